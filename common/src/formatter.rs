@@ -1,48 +1,282 @@
 use std::env;
+use std::fs;
 use std::path::{Path, PathBuf};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
 use crate::json::Workspace;
+use crate::sort;
+use crate::template::{Align, Column, Field, Part, Template, Width};
 
-pub fn fmt(workspaces: &Vec<Workspace>) -> Vec<(String, Workspace)> {
+// Byte range of a column's value within a formatted row, padding excluded
+#[derive(Clone, Debug, PartialEq)]
+pub struct Span {
+    // Field name from the template, or "list" for the list prefix of fmt_lists
+    pub column: &'static str,
+    pub start: usize,
+    pub end: usize,
+}
+
+// A formatted workspace and where each of its columns is
+#[derive(Clone, Debug)]
+pub struct Row {
+    pub text: String,
+    pub spans: Vec<Span>,
+    pub workspace: Workspace,
+}
+
+// Formats each workspace as a row of `template`, padding aligned columns to the widest value
+pub fn fmt(workspaces: &[Workspace], template: &Template) -> Vec<(String, Workspace)> {
+    fmt_rows(workspaces, template)
+        .into_iter()
+        .map(|row| (row.text, row.workspace))
+        .collect()
+}
+
+// Same as fmt, keeping the position of each column for highlighting
+pub fn fmt_rows(workspaces: &[Workspace], template: &Template) -> Vec<Row> {
     if workspaces.is_empty() {
         return Vec::new();
     }
-    let fmt_vals = workspaces
+    let parts = template.parts_for(workspaces);
+
+    // Values of each column, truncated to their max width
+    let values: Vec<Vec<String>> = parts
         .iter()
-        .map(|ws| {
-            (
-                Workspace {
-                    name: ws.name.to_string(),
-                    path: fmt_path(ws.path.to_string()),
-                },
-                ws.clone(),
-            )
+        .map(|part| match part {
+            Part::Text(_) => Vec::new(),
+            Part::Column(column) => workspaces
+                .iter()
+                .enumerate()
+                .map(|(idx, ws)| {
+                    let value = field_value(column.field, ws, idx, template);
+                    match (column.max.or(template.max_width), column.field) {
+                        (None, _) => value,
+                        (Some(max), Field::Path) => truncate_path(&value, max),
+                        (Some(max), _) => truncate(&value, max),
+                    }
+                })
+                .collect(),
         })
-        .collect::<Vec<(Workspace, Workspace)>>();
+        .collect();
+    let widths: Vec<usize> = parts
+        .iter()
+        .zip(&values)
+        .map(|(part, column_values)| match part {
+            Part::Column(Column {
+                width: Width::Fixed(width),
+                ..
+            }) => *width,
+            Part::Column(Column { width: Width::Auto, .. }) => column_values
+                .iter()
+                .map(|value| display_width(value))
+                .max()
+                .unwrap_or(0),
+            _ => 0,
+        })
+        .collect();
+
+    workspaces
+        .iter()
+        .enumerate()
+        .map(|(idx, ws)| {
+            let mut row = Row {
+                text: String::new(),
+                spans: Vec::new(),
+                workspace: ws.to_owned(),
+            };
+            for ((part, column_values), width) in parts.iter().zip(&values).zip(&widths) {
+                match part {
+                    Part::Text(text) => row.text.push_str(text),
+                    Part::Column(column) => {
+                        push_column(&mut row, column.field.name(), &column_values[idx], column.align, *width)
+                    }
+                }
+            }
+            row
+        })
+        .collect()
+}
 
-    let longest_name = fmt_vals.iter().map(|(fmt, _)| fmt.name.len()).max().unwrap_or(0);
-    let longest_path = fmt_vals.iter().map(|(fmt, _)| fmt.path.len()).max().unwrap_or(0);
+// Appends a value padded by display width to the row, recording where the value itself is
+// std's format! pads by chars which misaligns wide chars
+fn push_column(row: &mut Row, column: &'static str, value: &str, align: Align, width: usize) {
+    let fill = width.saturating_sub(display_width(value));
+    let (left, right) = match align {
+        Align::Left => (0, fill),
+        Align::Right => (fill, 0),
+        Align::Center => (fill / 2, fill - fill / 2),
+    };
+    row.text.push_str(&" ".repeat(left));
+    let start = row.text.len();
+    row.text.push_str(value);
+    if !value.is_empty() {
+        row.spans.push(Span {
+            column,
+            start,
+            end: row.text.len(),
+        });
+    }
+    row.text.push_str(&" ".repeat(right));
+}
 
-    fmt_vals
+// Formats workspaces from several lists together, prefixing each with its list name
+// Returns (row, list name)
+pub fn fmt_lists(lists: &[(String, Vec<Workspace>)], template: &Template) -> Vec<(Row, String)> {
+    let list_names: Vec<&String> = lists
         .iter()
-        .map(|(fmt_ws, orig_ws)| {
-            let path = Path::new(&orig_ws.path); // Use original path for directory check
-            let icon = if path.is_dir() { "📁" } else { "📄" };
-
-            let formatted_string = format!(
-                "[ {} ] - [ {} ] - [ {} ]",
-                icon,
-                pad_right(fmt_ws.name.to_string(), longest_name),
-                pad_right(fmt_ws.path.to_string(), longest_path)
-            );
-
-            (formatted_string, orig_ws.to_owned())
+        .flat_map(|(name, workspaces)| workspaces.iter().map(move |_| name))
+        .collect();
+    let workspaces: Vec<Workspace> = lists.iter().flat_map(|(_, workspaces)| workspaces.clone()).collect();
+    let longest_list = list_names.iter().map(|name| display_width(name)).max().unwrap_or(0);
+
+    fmt_rows(&workspaces, template)
+        .into_iter()
+        .zip(list_names)
+        .map(|(ws_row, list_name)| {
+            let mut row = Row {
+                text: "[ ".to_string(),
+                spans: Vec::new(),
+                workspace: ws_row.workspace,
+            };
+            push_column(&mut row, "list", list_name, Align::Left, longest_list);
+            row.text.push_str(" ] - ");
+            let offset = row.text.len();
+            row.text.push_str(&ws_row.text);
+            row.spans.extend(ws_row.spans.into_iter().map(|span| Span {
+                start: span.start + offset,
+                end: span.end + offset,
+                ..span
+            }));
+            (row, list_name.to_string())
         })
         .collect()
 }
 
-fn pad_right(s: String, width: usize) -> String {
-    format!("{:<width$}", s, width = width)
+fn field_value(field: Field, ws: &Workspace, idx: usize, template: &Template) -> String {
+    match field {
+        Field::Icon => template.icons.icon(&ws.path),
+        Field::Name => ws.name.to_string(),
+        Field::Path => fmt_path(ws.path.to_string()),
+        Field::Tags => ws.tags.join(", "),
+        Field::LastOpened => ws.last_opened.map(fmt_age).unwrap_or_default(),
+        Field::GitBranch => git_branch(Path::new(&ws.path)).unwrap_or_default(),
+        Field::Index => (idx + 1).to_string(),
+    }
+}
+
+// Columns the string takes up in a terminal, wide chars such as CJK and emoji take two
+pub fn display_width(value: &str) -> usize {
+    UnicodeWidthStr::width(value)
+}
+
+// The longest start of `value` that fits in `width` columns
+fn take_width(value: &str, width: usize) -> &str {
+    let mut used = 0;
+    for (idx, c) in value.char_indices() {
+        used += c.width().unwrap_or(0);
+        if used > width {
+            return &value[..idx];
+        }
+    }
+    value
+}
+
+// The longest end of `value` that fits in `width` columns
+fn take_width_end(value: &str, width: usize) -> &str {
+    let mut used = 0;
+    for (idx, c) in value.char_indices().rev() {
+        used += c.width().unwrap_or(0);
+        if used > width {
+            return &value[idx + c.len_utf8()..];
+        }
+    }
+    value
+}
+
+// Cuts values wider than `max` columns, ending them with an ellipsis
+fn truncate(value: &str, max: usize) -> String {
+    match display_width(value) <= max {
+        true => value.to_string(),
+        false if max == 0 => String::new(),
+        false => format!("{}…", take_width(value, max - 1)),
+    }
+}
+
+// Shortens a path wider than `max` columns in the middle, keeping whole components from each end:
+// ~/src/company/team/service/api -> ~/src/…/service/api
+pub fn truncate_path(path: &str, max: usize) -> String {
+    if display_width(path) <= max {
+        return path.to_string();
+    }
+    let components: Vec<&str> = path.split('/').collect();
+    let joined = |head: usize, tail: usize| {
+        let mut parts = components[..head].to_vec();
+        parts.push("…");
+        parts.extend(&components[components.len() - tail..]);
+        parts.join("/")
+    };
+
+    let (mut head, mut tail) = (1, 1);
+    if components.len() < 3 || display_width(&joined(head, tail)) > max {
+        // Too few or too wide components, cut the middle of the text instead
+        let end = take_width_end(path, max.saturating_sub(1) / 2);
+        let start = take_width(path, max.saturating_sub(1 + display_width(end)));
+        return match max {
+            0 => String::new(),
+            _ => format!("{start}…{end}"),
+        };
+    }
+    // Grow the kept ends a component at a time, alternating and starting with the end as it names the workspace
+    while head + tail + 1 < components.len() {
+        let grow_tail = display_width(&joined(head, tail + 1)) <= max;
+        let grow_head = display_width(&joined(head + 1, tail)) <= max;
+        match (grow_tail, grow_head) {
+            (true, true) if tail <= head => tail += 1,
+            (_, true) => head += 1,
+            (true, false) => tail += 1,
+            (false, false) => break,
+        }
+    }
+    joined(head, tail)
+}
+
+// Time since a unix timestamp, e.g. "5m ago"
+fn fmt_age(timestamp: u64) -> String {
+    let age = sort::now().saturating_sub(timestamp);
+    match age {
+        0..60 => "just now".to_string(),
+        60..3_600 => format!("{}m ago", age / 60),
+        3_600..86_400 => format!("{}h ago", age / 3_600),
+        86_400..604_800 => format!("{}d ago", age / 86_400),
+        _ => format!("{}w ago", age / 604_800),
+    }
+}
+
+// Branch checked out in the git repository containing `path`, a short hash when detached
+fn git_branch(path: &Path) -> Option<String> {
+    let dot_git = path
+        .ancestors()
+        .map(|dir| dir.join(".git"))
+        .find(|dot_git| dot_git.exists())?;
+    // Worktrees and submodules have a .git file pointing at the git dir
+    let git_dir = match dot_git.is_file() {
+        true => {
+            let contents = fs::read_to_string(&dot_git).ok()?;
+            let git_dir = PathBuf::from(contents.strip_prefix("gitdir:")?.trim());
+            match git_dir.is_relative() {
+                true => dot_git.parent()?.join(git_dir),
+                false => git_dir,
+            }
+        }
+        false => dot_git,
+    };
+
+    let head = fs::read_to_string(git_dir.join("HEAD")).ok()?;
+    let head = head.trim();
+    match head.strip_prefix("ref: ") {
+        Some(reference) => Some(reference.strip_prefix("refs/heads/").unwrap_or(reference).to_string()),
+        None => Some(head.chars().take(7).collect()),
+    }
 }
 
 /// Formats a raw string by removing surrounding whitespace and quotes.
@@ -88,6 +322,12 @@ pub fn unfmt_path(path: String) -> String {
     expanded_path.to_string_lossy().to_string()
 }
 
+// Expands ~ and resolves symlinks and relative parts, paths that don't exist are only expanded
+pub fn canonical_path(path: &str) -> PathBuf {
+    let expanded = PathBuf::from(unfmt_path(path.to_string()));
+    fs::canonicalize(&expanded).unwrap_or(expanded)
+}
+
 // Formats a path replacing home dir with ~
 pub fn fmt_path(path: String) -> String {
     if let Some(home) = home_dir() {
@@ -111,3 +351,44 @@ pub fn home_dir() -> Option<PathBuf> {
         env::var_os("HOME").map(PathBuf::from)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn workspace(name: &str) -> Workspace {
+        Workspace::new(name.to_string(), format!("/missing/{name}"))
+    }
+
+    #[test]
+    fn pads_by_display_width() {
+        let template = Template::parse("{name:<}|{name:>6}|").unwrap();
+        let rows = fmt(&[workspace("日本"), workspace("café")], &template);
+        assert_eq!(rows[0].0, "日本|  日本|");
+        assert_eq!(rows[1].0, "café|  café|");
+        assert_eq!(truncate("日本語", 5), "日本…");
+    }
+
+    #[test]
+    fn spans_cover_column_values() {
+        let template = Template::parse("{index:>2} {name:<} [{tags}]").unwrap();
+        let lists = vec![("work".to_string(), vec![workspace("日本"), workspace("a")])];
+        let (row, _) = &fmt_lists(&lists, &template)[0];
+        let columns: Vec<(&str, &str)> = row
+            .spans
+            .iter()
+            .map(|span| (span.column, &row.text[span.start..span.end]))
+            .collect();
+        assert_eq!(row.text, "[ work ] -  1 日本 []");
+        assert_eq!(columns, vec![("list", "work"), ("index", "1"), ("name", "日本")]);
+    }
+
+    #[test]
+    fn paths_are_shortened_in_the_middle() {
+        let path = "~/src/company/team/service/api";
+        assert_eq!(truncate_path(path, 40), path);
+        assert_eq!(truncate_path(path, 20), "~/src/…/service/api");
+        assert_eq!(truncate_path(path, 8), "~/…/api");
+        assert_eq!(truncate_path("/averyveryverylongname", 9), "/ave…name");
+    }
+}
@@ -0,0 +1,252 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum IconSet {
+    #[default]
+    Emoji,
+    // Glyphs from a patched Nerd Font
+    NerdFont,
+    // For terminals and consoles that can't show emoji
+    Ascii,
+}
+
+impl fmt::Display for IconSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IconSet::Emoji => write!(f, "emoji"),
+            IconSet::NerdFont => write!(f, "nerdfont"),
+            IconSet::Ascii => write!(f, "ascii"),
+        }
+    }
+}
+
+impl FromStr for IconSet {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "emoji" => Ok(IconSet::Emoji),
+            "nerdfont" => Ok(IconSet::NerdFont),
+            "ascii" => Ok(IconSet::Ascii),
+            _ => Err(format!("Unknown icon set: '{s}' (expected emoji, nerdfont or ascii)")),
+        }
+    }
+}
+
+// What a workspace path points at, most specific first
+#[derive(Clone, Debug, PartialEq)]
+pub enum IconKind {
+    Url,
+    // ssh://, sftp:// and scp style user@host:path
+    Remote,
+    Missing,
+    Symlink,
+    GitRepo,
+    Directory,
+    // With the lowercase extension, if any
+    File(Option<String>),
+}
+
+impl IconKind {
+    pub fn of(path: &str) -> IconKind {
+        if path.starts_with("http://") || path.starts_with("https://") {
+            return IconKind::Url;
+        }
+        if path.contains("://") || is_scp_path(path) {
+            return IconKind::Remote;
+        }
+
+        // A trailing separator follows a symlink to its target, so dir/ of a symlinked dir would show as a directory
+        let trimmed = path.trim_end_matches(['/', '\\']);
+        let path = Path::new(if trimmed.is_empty() { path } else { trimmed });
+        match path.symlink_metadata() {
+            Err(_) => IconKind::Missing,
+            Ok(meta) if meta.is_symlink() => IconKind::Symlink,
+            Ok(meta) if meta.is_dir() && path.join(".git").exists() => IconKind::GitRepo,
+            Ok(meta) if meta.is_dir() => IconKind::Directory,
+            Ok(_) => IconKind::File(path.extension().map(|ext| ext.to_string_lossy().to_lowercase())),
+        }
+    }
+
+    // Key used to override the icon in the config
+    pub fn name(&self) -> &str {
+        match self {
+            IconKind::Url => "url",
+            IconKind::Remote => "remote",
+            IconKind::Missing => "missing",
+            IconKind::Symlink => "symlink",
+            IconKind::GitRepo => "git_repo",
+            IconKind::Directory => "directory",
+            IconKind::File(_) => "file",
+        }
+    }
+}
+
+// user@host:path, but not C:\path on Windows
+fn is_scp_path(path: &str) -> bool {
+    match path.split_once(':') {
+        Some((host, _)) => host.contains('@') && !host.contains('/'),
+        None => false,
+    }
+}
+
+// The icon set with the overrides from the config
+// Overrides are keyed by kind name (directory, git_repo, file, missing, symlink, url, remote) or file extension (rs)
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Icons {
+    pub set: IconSet,
+    pub overrides: HashMap<String, String>,
+}
+
+impl Icons {
+    pub fn icon(&self, path: &str) -> String {
+        let kind = IconKind::of(path);
+        if let IconKind::File(Some(ext)) = &kind
+            && let Some(icon) = self
+                .overrides
+                .get(ext)
+                .map(String::to_string)
+                .or_else(|| self.extension(ext))
+        {
+            return icon;
+        }
+        match self.overrides.get(kind.name()) {
+            Some(icon) => icon.to_string(),
+            None => self.kind(&kind).to_string(),
+        }
+    }
+
+    fn kind(&self, kind: &IconKind) -> &'static str {
+        match (self.set, kind) {
+            (IconSet::Emoji, IconKind::Url) => "🌐",
+            (IconSet::Emoji, IconKind::Remote) => "📡",
+            (IconSet::Emoji, IconKind::Missing) => "❓",
+            (IconSet::Emoji, IconKind::Symlink) => "🔗",
+            (IconSet::Emoji, IconKind::GitRepo) => "🌿",
+            (IconSet::Emoji, IconKind::Directory) => "📁",
+            (IconSet::Emoji, IconKind::File(_)) => "📄",
+            (IconSet::NerdFont, IconKind::Url) => "\u{f0ac}",
+            (IconSet::NerdFont, IconKind::Remote) => "\u{f233}",
+            (IconSet::NerdFont, IconKind::Missing) => "\u{f071}",
+            (IconSet::NerdFont, IconKind::Symlink) => "\u{f0c1}",
+            (IconSet::NerdFont, IconKind::GitRepo) => "\u{e702}",
+            (IconSet::NerdFont, IconKind::Directory) => "\u{f07b}",
+            (IconSet::NerdFont, IconKind::File(_)) => "\u{f15b}",
+            (IconSet::Ascii, IconKind::Url) => "u",
+            (IconSet::Ascii, IconKind::Remote) => "r",
+            (IconSet::Ascii, IconKind::Missing) => "?",
+            (IconSet::Ascii, IconKind::Symlink) => "l",
+            (IconSet::Ascii, IconKind::GitRepo) => "g",
+            (IconSet::Ascii, IconKind::Directory) => "d",
+            (IconSet::Ascii, IconKind::File(_)) => "f",
+        }
+    }
+
+    // Files without an icon for their extension use the file icon
+    fn extension(&self, ext: &str) -> Option<String> {
+        let icon = match (self.set, ext) {
+            (IconSet::Emoji, "rs") => "🦀",
+            (IconSet::Emoji, "md" | "txt") => "📝",
+            (IconSet::Emoji, "lua") => "🌙",
+            (IconSet::Emoji, "py") => "🐍",
+            (IconSet::Emoji, "json" | "toml" | "yaml" | "yml") => "🔧",
+            (IconSet::Emoji, "sh" | "bash" | "zsh" | "fish") => "🐚",
+            (IconSet::NerdFont, "rs") => "\u{e7a8}",
+            (IconSet::NerdFont, "md") => "\u{e73e}",
+            (IconSet::NerdFont, "lua") => "\u{e620}",
+            (IconSet::NerdFont, "py") => "\u{e73c}",
+            (IconSet::NerdFont, "js") => "\u{e74e}",
+            (IconSet::NerdFont, "ts") => "\u{e628}",
+            (IconSet::NerdFont, "go") => "\u{e627}",
+            (IconSet::NerdFont, "c" | "h") => "\u{e61e}",
+            (IconSet::NerdFont, "json") => "\u{e60b}",
+            (IconSet::NerdFont, "toml" | "yaml" | "yml") => "\u{e615}",
+            (IconSet::NerdFont, "sh" | "bash" | "zsh" | "fish") => "\u{f489}",
+            (IconSet::NerdFont, "vim") => "\u{e62b}",
+            _ => return None,
+        };
+        Some(icon.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path_str(path: &Path) -> String {
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn kinds_come_from_the_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path().join("repo");
+        std::fs::create_dir_all(repo.join(".git")).unwrap();
+        let file = dir.path().join("main.RS");
+        std::fs::write(&file, "").unwrap();
+
+        assert_eq!(IconKind::of("https://example.com"), IconKind::Url);
+        assert_eq!(IconKind::of("ssh://host/srv"), IconKind::Remote);
+        assert_eq!(IconKind::of("user@host:srv"), IconKind::Remote);
+        assert_eq!(IconKind::of(&path_str(&dir.path().join("nope"))), IconKind::Missing);
+        assert_eq!(IconKind::of(&path_str(dir.path())), IconKind::Directory);
+        assert_eq!(IconKind::of(&path_str(&repo)), IconKind::GitRepo);
+        assert_eq!(IconKind::of(&path_str(&file)), IconKind::File(Some("rs".to_string())));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinked_dirs_are_symlinks_with_a_trailing_separator() {
+        let dir = tempfile::tempdir().unwrap();
+        let link = dir.path().join("link");
+        std::os::unix::fs::symlink(dir.path(), &link).unwrap();
+
+        assert_eq!(IconKind::of(&path_str(&link)), IconKind::Symlink);
+        assert_eq!(IconKind::of(&format!("{}/", path_str(&link))), IconKind::Symlink);
+        assert_eq!(IconKind::of("/"), IconKind::Directory);
+    }
+
+    #[test]
+    fn icons_come_from_the_set_and_overrides() {
+        let mut icons = Icons {
+            set: IconSet::Ascii,
+            overrides: HashMap::new(),
+        };
+        assert_eq!(icons.icon("https://example.com"), "u");
+        assert_eq!(icons.icon("/no/such/file.rs"), "?");
+
+        icons.set = IconSet::NerdFont;
+        assert_eq!(icons.icon("https://example.com"), "\u{f0ac}");
+
+        icons.set = IconSet::Emoji;
+        icons.overrides.insert("url".to_string(), "W".to_string());
+        assert_eq!(icons.icon("https://example.com"), "W");
+        assert_eq!(icons.icon("ssh://host/srv"), "📡");
+        assert_eq!("NerdFont".parse::<IconSet>(), Ok(IconSet::NerdFont));
+        assert!("fancy".parse::<IconSet>().is_err());
+    }
+
+    #[test]
+    fn extensions_have_their_own_icons() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("main.rs");
+        std::fs::write(&file, "").unwrap();
+        let other = dir.path().join("notes.unknown");
+        std::fs::write(&other, "").unwrap();
+
+        let mut icons = Icons::default();
+        assert_eq!(icons.icon(&path_str(&file)), "🦀");
+        assert_eq!(icons.icon(&path_str(&other)), "📄");
+
+        icons.overrides.insert("rs".to_string(), "R".to_string());
+        icons.overrides.insert("file".to_string(), "F".to_string());
+        assert_eq!(icons.icon(&path_str(&file)), "R");
+        assert_eq!(icons.icon(&path_str(&other)), "F");
+    }
+}
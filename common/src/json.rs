@@ -1,21 +1,84 @@
 use serde::Serialize;
-use std::fmt::{self, format};
+use std::collections::HashSet;
+use std::fmt;
 use std::fs::File;
-use std::io::{BufReader, BufWriter};
+use std::io::{BufWriter, Write};
 use std::io::{Error, ErrorKind};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
+use log::info;
 use serde::Deserialize;
 
+use crate::formatter;
 use crate::setup::path;
+use crate::sort;
+use crate::storage::{self, Backend, StoredList};
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct Workspace {
+    // Missing on lists written before ids existed, derived by `read_workspaces` until the list is next written
+    #[serde(rename = "Id", default)]
+    pub id: String,
+
     #[serde(rename = "Name")]
     pub name: String,
 
     #[serde(rename = "Path")]
     pub path: String,
+
+    #[serde(rename = "Tags", default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+
+    #[serde(rename = "OpenCount", default, skip_serializing_if = "is_zero")]
+    pub open_count: u32,
+
+    // Unix timestamp in seconds
+    #[serde(rename = "LastOpened", default, skip_serializing_if = "Option::is_none")]
+    pub last_opened: Option<u64>,
+}
+
+fn is_zero(count: &u32) -> bool {
+    *count == 0
+}
+
+impl Workspace {
+    pub fn new(name: String, path: String) -> Workspace {
+        Workspace {
+            id: new_id(),
+            name,
+            path,
+            tags: Vec::new(),
+            open_count: 0,
+            last_opened: None,
+        }
+    }
+
+    // True when the workspace has every one of the tags
+    pub fn has_tags(&self, tags: &[String]) -> bool {
+        tags.iter().all(|tag| self.tags.contains(tag))
+    }
+
+    // Returns false when the workspace already had the tag
+    pub fn add_tag(&mut self, tag: &str) -> bool {
+        if self.tags.iter().any(|t| t == tag) {
+            return false;
+        }
+        self.tags.push(tag.to_string());
+        true
+    }
+
+    // Returns false when the workspace didn't have the tag
+    pub fn remove_tag(&mut self, tag: &str) -> bool {
+        let count = self.tags.len();
+        self.tags.retain(|t| t != tag);
+        count != self.tags.len()
+    }
+
+    // Records an open for frecency sorting
+    pub fn mark_opened(&mut self) {
+        self.open_count += 1;
+        self.last_opened = Some(sort::now());
+    }
 }
 
 impl fmt::Display for Workspace {
@@ -24,36 +87,479 @@ impl fmt::Display for Workspace {
     }
 }
 
-const APP_NAME: &str = "workspacers";
+#[derive(Debug)]
+pub enum ReadError {
+    NotFound(PathBuf),
+    PermissionDenied(PathBuf),
+    Parse {
+        file: PathBuf,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    Io(PathBuf, Error),
+    // Written by a newer workspacers with a schema this one doesn't know
+    UnsupportedVersion(PathBuf, u32),
+}
+
+impl ReadError {
+    pub(crate) fn from_io(file: &Path, err: Error) -> ReadError {
+        match err.kind() {
+            ErrorKind::NotFound => ReadError::NotFound(file.to_path_buf()),
+            ErrorKind::PermissionDenied => ReadError::PermissionDenied(file.to_path_buf()),
+            _ => ReadError::Io(file.to_path_buf(), err),
+        }
+    }
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReadError::NotFound(file) => write!(f, "Workspace file not found: '{}'", file.to_string_lossy()),
+            ReadError::PermissionDenied(file) => {
+                write!(f, "Permission denied reading: '{}'", file.to_string_lossy())
+            }
+            ReadError::Parse {
+                file,
+                line,
+                column,
+                message,
+            } => write!(
+                f,
+                "Could not parse '{}' at line {line}, column {column}: {message}",
+                file.to_string_lossy()
+            ),
+            ReadError::Io(file, err) => write!(f, "Could not read '{}': {err}", file.to_string_lossy()),
+            ReadError::UnsupportedVersion(file, version) => write!(
+                f,
+                "'{}' has schema version {version}, this version of workspacers supports up to {SCHEMA_VERSION}. Please update workspacers",
+                file.to_string_lossy()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ReadError {}
+
+pub const APP_NAME: &str = "workspacers";
+
+// Version written with every list, older lists are upgraded when next written
+// 1: bare array of workspaces, 2: { "version": 2, "workspaces": [...] }
+pub const SCHEMA_VERSION: u32 = 2;
+
+pub fn new_id() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
 
 pub fn get_json_dir(json_arg: Option<PathBuf>) -> Result<PathBuf, Error> {
-    Ok(json_arg.unwrap_or(path::get_data_dir(APP_NAME)?))
+    match json_arg {
+        Some(json_dir) => Ok(json_dir),
+        None => path::get_data_dir(APP_NAME),
+    }
+}
+
+// The list's existing file in any format, a new list uses `backend`
+pub fn get_list_file(json_dir: &Path, ws_name: &str, backend: Backend) -> PathBuf {
+    Backend::ALL
+        .iter()
+        .map(|existing| json_dir.join(format!("{ws_name}.{}", existing.extension())))
+        .find(|file| file.is_file())
+        .unwrap_or_else(|| json_dir.join(format!("{ws_name}.{}", backend.extension())))
+}
+
+// Name of the list stored in `file`, None for files that aren't lists such as locks and backups
+pub fn get_list_name(file: &Path) -> Option<String> {
+    Backend::from_file(file)?;
+    file.file_stem().map(|name| name.to_string_lossy().to_string())
+}
+
+// Names of every list(*.json, *.toml, *.db) in the json dir, sorted
+pub fn get_list_names(json_dir: &Path) -> Result<Vec<String>, ReadError> {
+    let mut names = Vec::new();
+    for entry in std::fs::read_dir(json_dir).map_err(|err| ReadError::from_io(json_dir, err))? {
+        let path = entry.map_err(|err| ReadError::from_io(json_dir, err))?.path();
+        if path.is_file()
+            && let Some(name) = get_list_name(&path)
+        {
+            names.push(name);
+        }
+    }
+    names.sort();
+    // The same list in several formats is only read once, see `get_list_file`
+    names.dedup();
+    Ok(names)
+}
+
+// Reads every list in the json dir, paired with its list name
+// Fails on the first list that can't be read rather than hiding it
+pub fn read_all_workspaces(json_dir: &Path) -> Result<Vec<(String, Vec<Workspace>)>, ReadError> {
+    get_list_names(json_dir)?
+        .into_iter()
+        .map(|name| {
+            let workspaces = read_workspaces(&get_list_file(json_dir, &name, Backend::default()))?;
+            Ok((name, workspaces))
+        })
+        .collect()
+}
+
+// Workspaces with every one of the tags, all workspaces when no tags given
+pub fn filter_by_tags(workspaces: &[Workspace], tags: &[String]) -> Vec<Workspace> {
+    workspaces.iter().filter(|ws| ws.has_tags(tags)).cloned().collect()
+}
+
+// The workspace containing `path`, with the list it is in. When workspaces are nested the deepest wins
+// Paths are canonicalised and compared by component, so /src/app doesn't contain /src/application
+pub fn find_current(lists: &[(String, Vec<Workspace>)], path: &str) -> Option<(String, Workspace)> {
+    let path = formatter::canonical_path(path);
+    lists
+        .iter()
+        .flat_map(|(list, workspaces)| workspaces.iter().map(move |ws| (list, ws)))
+        .filter_map(|(list, ws)| {
+            let ws_path = formatter::canonical_path(&ws.path);
+            path.starts_with(&ws_path)
+                .then(|| (ws_path.components().count(), list, ws))
+        })
+        // max_by_key keeps the last of equal matches, reverse so the first list wins
+        .rev()
+        .max_by_key(|(depth, _, _)| *depth)
+        .map(|(_, list, ws)| (list.to_string(), ws.clone()))
+}
+
+pub fn position_by_id(workspaces: &[Workspace], id: &str) -> Option<usize> {
+    workspaces.iter().position(|ws| ws.id == id)
+}
+
+// Entries without an id (or sharing one) are given an id derived from the workspace, the file isn't written
+// The ids are saved by the next change through `WorkspaceStore`, which writes under the list lock
+// The format is picked by the file's extension, see `storage::Backend`
+pub fn read_workspaces(json_file: &Path) -> Result<Vec<Workspace>, ReadError> {
+    let mut workspaces = read_stored(json_file)?.workspaces;
+    if migrate_ids(&mut workspaces) {
+        info!("Assigned missing ids in: {}", json_file.to_string_lossy());
+    }
+    Ok(workspaces)
+}
+
+fn read_stored(json_file: &Path) -> Result<StoredList, ReadError> {
+    let list = storage::for_file(json_file).read(json_file)?;
+    if list.version > SCHEMA_VERSION {
+        return Err(ReadError::UnsupportedVersion(json_file.to_path_buf(), list.version));
+    }
+    Ok(list)
 }
 
-pub fn get_json_file(json_dir: &PathBuf, ws_name: &str) -> PathBuf {
-    json_dir.join(format!("{ws_name}.json"))
+// A list that doesn't exist yet is empty, any other error is returned
+pub fn read_workspaces_or_empty(json_file: &Path) -> Result<Vec<Workspace>, ReadError> {
+    match read_workspaces(json_file) {
+        Err(ReadError::NotFound(_)) => Ok(Vec::new()),
+        result => result,
+    }
 }
 
-// Retuns an empty Vec when file not found or malformed
-pub fn read_workspaces(json_file: &PathBuf) -> Vec<Workspace> {
-    let file = match File::open(&json_file) {
-        Ok(file) => file,
-        Err(_) => return Vec::new(),
+// Returns true when any workspace was given a new id
+// The ids are derived from the workspace, so they are the same on every read until the list is written with them
+fn migrate_ids(workspaces: &mut [Workspace]) -> bool {
+    let mut seen = HashSet::new();
+    let mut changed = false;
+    for ws in workspaces.iter_mut() {
+        if ws.id.is_empty() || !seen.insert(ws.id.clone()) {
+            ws.id = (0..)
+                .map(|attempt| derived_id(ws, attempt))
+                .find(|id| !seen.contains(id))
+                .unwrap_or_else(new_id);
+            seen.insert(ws.id.clone());
+            changed = true;
+        }
+    }
+    changed
+}
+
+// A uuid shaped FNV-1a hash of the name and path, which are unique within a list
+// `attempt` is bumped for hand edited lists repeating a workspace
+fn derived_id(ws: &Workspace, attempt: u64) -> String {
+    let hash = |seed: u64| {
+        let bytes = ws
+            .name
+            .bytes()
+            .chain([0])
+            .chain(ws.path.bytes())
+            .chain(attempt.to_le_bytes());
+        bytes.fold(seed, |hash, byte| (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3))
     };
+    let (high, low) = (hash(0xcbf29ce484222325), hash(0x84222325cbf29ce4));
+    format!(
+        "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+        high >> 32,
+        (high >> 16) & 0xffff,
+        high & 0xffff,
+        low >> 48,
+        low & 0xffff_ffff_ffff
+    )
+}
 
-    match serde_json::from_reader(BufReader::new(file)) {
-        Ok(workspaces) => workspaces,
-        Err(_) => Vec::new(),
+// An upgrade of a list to the current schema, planned without writing so it can be reviewed first
+pub struct Migration {
+    pub json_file: PathBuf,
+    pub from_version: u32,
+    // Contents of the list before and after, None for binary formats
+    pub before: Option<String>,
+    pub after: Option<String>,
+    read: Vec<Workspace>,
+    upgraded: Vec<Workspace>,
+}
+
+impl Migration {
+    pub fn is_needed(&self) -> bool {
+        self.from_version < SCHEMA_VERSION || self.read != self.upgraded || self.before != self.after
+    }
+}
+
+pub fn plan_migration(json_file: &Path) -> Result<Migration, ReadError> {
+    let list = read_stored(json_file)?;
+    let mut upgraded = list.workspaces.clone();
+    migrate_ids(&mut upgraded);
+    let after = storage::for_file(json_file)
+        .render(&upgraded)
+        .map_err(|err| ReadError::Io(json_file.to_path_buf(), err))?;
+    let before = match after {
+        Some(_) => Some(std::fs::read_to_string(json_file).map_err(|err| ReadError::from_io(json_file, err))?),
+        None => None,
+    };
+    Ok(Migration {
+        json_file: json_file.to_path_buf(),
+        from_version: list.version,
+        before,
+        after,
+        read: list.workspaces,
+        upgraded,
+    })
+}
+
+// Writes a planned migration, fails if the list was changed since it was planned
+pub fn apply_migration(migration: &Migration) -> Result<(), Error> {
+    let _lock = ListLock::acquire(&migration.json_file)?;
+    let latest = read_stored(&migration.json_file).map_err(Error::other)?;
+    if latest.version != migration.from_version || latest.workspaces != migration.read {
+        return Err(Error::other(
+            "List changed since the migration was planned, please run it again",
+        ));
+    }
+    write_workspaces(&migration.json_file, &migration.upgraded)
+}
+
+pub fn get_lock_file(json_file: &Path) -> PathBuf {
+    let mut file_name = json_file.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".lock");
+    json_file.with_file_name(file_name)
+}
+
+// Holds an advisory lock on <name>.json.lock until dropped, shared by workspacers-cli and workspacers-nvim
+// A separate lock file is used as the list itself is replaced on every write
+pub(crate) struct ListLock {
+    file: File,
+}
+
+impl ListLock {
+    pub(crate) fn acquire(json_file: &Path) -> Result<ListLock, Error> {
+        let file = File::options()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(get_lock_file(json_file))?;
+        file.lock()?;
+        Ok(ListLock { file })
+    }
+}
+
+impl Drop for ListLock {
+    fn drop(&mut self) {
+        let _ = self.file.unlock();
+    }
+}
+
+// Number of previous versions kept next to each list as <name>.json.<n>.bak, 1 being the newest
+pub const BACKUP_COUNT: usize = 5;
+
+pub fn get_backup_file(json_file: &Path, generation: usize) -> PathBuf {
+    let mut file_name = json_file.file_name().unwrap_or_default().to_os_string();
+    file_name.push(format!(".{generation}.bak"));
+    json_file.with_file_name(file_name)
+}
+
+// Copies a backup generation over the list, the current list is itself backed up first
+pub fn restore_backup(json_file: &Path, generation: usize) -> Result<(), Error> {
+    if !(1..=BACKUP_COUNT).contains(&generation) {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("Invalid backup generation: {generation} (expected 1 to {BACKUP_COUNT})"),
+        ));
+    }
+    let _lock = ListLock::acquire(json_file)?;
+    let backup_file = get_backup_file(json_file, generation);
+    let backup = std::fs::read(&backup_file).map_err(|err| {
+        Error::new(
+            err.kind(),
+            format!("Could not read backup {}: {err}", backup_file.to_string_lossy()),
+        )
+    })?;
+    rotate_backups(json_file)?;
+    write_bytes(json_file, &backup)
+}
+
+// If json path is not found, it will be created here, the previous list is kept as a backup
+// The format is picked by the file's extension, see `storage::Backend`
+pub fn write_workspaces(json_file: &Path, workspaces: &[Workspace]) -> Result<(), Error> {
+    rotate_backups(json_file)?;
+    storage::for_file(json_file).write(json_file, workspaces)
+}
+
+// Writes a change that only records usage, such as an open, without a backup
+// Backing these up would soon push every real edit out of the backups
+pub fn write_usage(json_file: &Path, workspaces: &[Workspace]) -> Result<(), Error> {
+    storage::for_file(json_file).write(json_file, workspaces)
+}
+
+// Writes to a temp file which is synced and renamed over the list, so a failed write leaves the list intact
+pub(crate) fn write_bytes(json_file: &Path, contents: &[u8]) -> Result<(), Error> {
+    let mut tmp_name = std::ffi::OsString::from(".");
+    tmp_name.push(json_file.file_name().unwrap_or_default());
+    tmp_name.push(format!(".{}.tmp", std::process::id()));
+    let tmp_file = json_file.with_file_name(tmp_name);
+
+    let result = write_synced(&tmp_file, contents).and_then(|_| std::fs::rename(&tmp_file, json_file));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp_file);
     }
+    result?;
+    sync_dir(json_file);
+    Ok(())
+}
+
+fn write_synced(file: &Path, contents: &[u8]) -> Result<(), Error> {
+    let mut writer = BufWriter::new(File::create(file)?);
+    writer.write_all(contents)?;
+    writer.into_inner().map_err(|err| err.into_error())?.sync_all()
 }
 
-// If json path is not found, it will be created here
-pub fn write_workspaces<T>(json_file: &PathBuf, workspaces: &Vec<T>) -> Result<(), Error>
-where
-    T: Serialize,
-{
-    let file = File::create(json_file)?;
-    serde_json::to_writer_pretty(BufWriter::new(file), workspaces)
-        .map_err(|err| Error::from(Error::new(ErrorKind::Other, err)))?;
+// Shifts <n>.bak to <n+1>.bak, dropping the oldest, and copies the current list to 1.bak
+pub(crate) fn rotate_backups(json_file: &Path) -> Result<(), Error> {
+    if !json_file.exists() {
+        return Ok(());
+    }
+    for generation in (1..BACKUP_COUNT).rev() {
+        let backup = get_backup_file(json_file, generation);
+        if backup.exists() {
+            std::fs::rename(&backup, get_backup_file(json_file, generation + 1))?;
+        }
+    }
+    std::fs::copy(json_file, get_backup_file(json_file, 1))?;
     Ok(())
 }
+
+// Persist the rename itself, directories can't be opened for syncing on Windows so this is best effort
+fn sync_dir(json_file: &Path) {
+    if let Some(dir) = json_file.parent()
+        && let Ok(dir) = File::open(dir)
+    {
+        let _ = dir.sync_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backups_keep_the_newest_generations() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("list.json");
+        let read = |file: &Path| std::fs::read_to_string(file).ok();
+        for version in 0..BACKUP_COUNT + 2 {
+            rotate_backups(&file).unwrap();
+            write_bytes(&file, version.to_string().as_bytes()).unwrap();
+        }
+        assert_eq!(read(&file).unwrap(), (BACKUP_COUNT + 1).to_string());
+        for generation in 1..=BACKUP_COUNT {
+            let version = BACKUP_COUNT + 1 - generation;
+            assert_eq!(read(&get_backup_file(&file, generation)).unwrap(), version.to_string());
+        }
+        // Version 0 was dropped
+        assert_eq!(read(&get_backup_file(&file, BACKUP_COUNT + 1)), None);
+
+        restore_backup(&file, 3).unwrap();
+        assert_eq!(read(&file).unwrap(), (BACKUP_COUNT - 2).to_string());
+        assert_eq!(
+            read(&get_backup_file(&file, 1)).unwrap(),
+            (BACKUP_COUNT + 1).to_string()
+        );
+        assert!(restore_backup(&file, 0).is_err());
+        assert!(restore_backup(&file, BACKUP_COUNT + 1).is_err());
+    }
+
+    #[test]
+    fn missing_ids_are_the_same_on_every_read() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("list.json");
+        std::fs::write(
+            &file,
+            r#"[{ "Name": "a", "Path": "/src/a" }, { "Id": "b1", "Name": "b", "Path": "/src/b" },
+                { "Id": "b1", "Name": "c", "Path": "/src/c" }]"#,
+        )
+        .unwrap();
+        let read = || {
+            let mut list = read_stored(&file).unwrap();
+            migrate_ids(&mut list.workspaces);
+            list.workspaces
+        };
+        let first = read();
+        assert_eq!(first, read());
+        assert_eq!(first[1].id, "b1");
+        assert!(!first[0].id.is_empty() && first[2].id != "b1" && first[0].id != first[2].id);
+    }
+
+    #[test]
+    fn reads_do_not_write_the_list() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("list.json");
+        let legacy = r#"[{ "Name": "a", "Path": "/src/a" }]"#;
+        std::fs::write(&file, legacy).unwrap();
+        let workspaces = read_workspaces(&file).unwrap();
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), legacy);
+
+        // The derived id is kept by the next change
+        let mut store = crate::store::WorkspaceStore::open(&file).unwrap();
+        store.add_tag(&workspaces[0].id, "rust").unwrap();
+        assert_eq!(read_stored(&file).unwrap().workspaces[0].id, workspaces[0].id);
+    }
+
+    fn ws(name: &str, path: &Path) -> Workspace {
+        Workspace::new(name.to_string(), path.to_string_lossy().to_string())
+    }
+
+    #[test]
+    fn current_is_the_deepest_match() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        std::fs::create_dir_all(root.join("src/app/lib")).unwrap();
+        std::fs::create_dir_all(root.join("src/application")).unwrap();
+        let lists = vec![
+            ("a".to_string(), vec![ws("src", &root.join("src"))]),
+            ("b".to_string(), vec![ws("app", &root.join("src/app"))]),
+        ];
+        let current = |path: &Path| find_current(&lists, &path.to_string_lossy()).map(|(list, ws)| (list, ws.name));
+
+        assert_eq!(current(&root.join("src/app/lib")), Some(("b".into(), "app".into())));
+        assert_eq!(current(&root.join("src/application")), Some(("a".into(), "src".into())));
+        assert_eq!(
+            current(&root.join("src/app/../application")),
+            Some(("a".into(), "src".into()))
+        );
+        assert_eq!(current(&root), None);
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(root.join("src/app"), root.join("link")).unwrap();
+            assert_eq!(current(&root.join("link/lib")), Some(("b".into(), "app".into())));
+        }
+    }
+}
@@ -1,3 +1,9 @@
 pub mod formatter;
+pub mod icons;
 pub mod json;
+pub mod session;
 pub mod setup;
+pub mod sort;
+pub mod storage;
+pub mod store;
+pub mod template;
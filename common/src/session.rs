@@ -0,0 +1,139 @@
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+// Directory in the json dir holding a session per workspace as <list>/<id>.json
+pub const SESSION_DIR: &str = "sessions";
+
+// Editor state of a workspace, saved when leaving it and restored when entering it again
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "PascalCase")]
+pub struct Session {
+    pub cwd: String,
+    // Listed buffers, including those not shown in a window
+    #[serde(default)]
+    pub buffers: Vec<String>,
+    pub layout: Layout,
+}
+
+// Windows of the tab page, as returned by Neovim's winlayout()
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum Layout {
+    Leaf(Window),
+    // Side by side
+    Row(Vec<Layout>),
+    // Stacked
+    Col(Vec<Layout>),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "PascalCase")]
+pub struct Window {
+    // Empty for windows without a file
+    #[serde(default)]
+    pub path: String,
+    // 1 based line and 0 based byte column, as nvim_win_get_cursor
+    pub line: u64,
+    pub col: u64,
+    pub width: u64,
+    pub height: u64,
+    #[serde(default)]
+    pub current: bool,
+}
+
+// Sessions are kept per list, as <list>/<id>.json, since the same id can be in more than one list
+// List names and ids are used as file names so can't leave the session dir
+pub fn get_session_file(json_dir: &Path, list: &str, id: &str) -> Result<PathBuf, Error> {
+    if list.is_empty() || list == "." || list == ".." || list.contains(['/', '\\']) {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("Invalid list name: '{list}'"),
+        ));
+    }
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("Invalid workspace id: '{id}'"),
+        ));
+    }
+    Ok(json_dir.join(SESSION_DIR).join(list).join(format!("{id}.json")))
+}
+
+// None when the workspace has no saved session
+pub fn read_session(json_dir: &Path, list: &str, id: &str) -> Result<Option<Session>, Error> {
+    let contents = match std::fs::read_to_string(get_session_file(json_dir, list, id)?) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err),
+    };
+    serde_json::from_str(&contents)
+        .map(Some)
+        .map_err(|err| Error::new(ErrorKind::InvalidData, format!("Invalid session for '{id}': {err}")))
+}
+
+// Written the same way as the lists, so a failed write leaves the old session intact
+pub fn write_session(json_dir: &Path, list: &str, id: &str, session: &Session) -> Result<(), Error> {
+    let file = get_session_file(json_dir, list, id)?;
+    if let Some(dir) = file.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let json = serde_json::to_string_pretty(session).map_err(Error::other)?;
+    crate::json::write_bytes(&file, json.as_bytes())
+}
+
+// Removing a session that was never saved is not an error
+pub fn remove_session(json_dir: &Path, list: &str, id: &str) -> Result<(), Error> {
+    match std::fs::remove_file(get_session_file(json_dir, list, id)?) {
+        Err(err) if err.kind() != ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(path: &str) -> Layout {
+        Layout::Leaf(Window {
+            path: path.to_string(),
+            line: 3,
+            col: 1,
+            width: 80,
+            height: 20,
+            current: false,
+        })
+    }
+
+    #[test]
+    fn sessions_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let session = Session {
+            cwd: "/src/app".to_string(),
+            buffers: vec!["/src/app/a.rs".to_string()],
+            layout: Layout::Row(vec![
+                window("/src/app/a.rs"),
+                Layout::Col(vec![window("/src/app/b.rs"), window("")]),
+            ]),
+        };
+        assert_eq!(read_session(dir.path(), "work", "id-1").unwrap(), None);
+        write_session(dir.path(), "work", "id-1", &session).unwrap();
+        assert_eq!(read_session(dir.path(), "work", "id-1").unwrap(), Some(session.clone()));
+        // The same id in another list has its own session
+        assert_eq!(read_session(dir.path(), "notes", "id-1").unwrap(), None);
+        write_session(dir.path(), "notes", "id-1", &session).unwrap();
+        remove_session(dir.path(), "work", "id-1").unwrap();
+        assert_eq!(read_session(dir.path(), "work", "id-1").unwrap(), None);
+        assert_eq!(read_session(dir.path(), "notes", "id-1").unwrap(), Some(session));
+        remove_session(dir.path(), "work", "id-1").unwrap();
+    }
+
+    #[test]
+    fn ids_cannot_leave_the_session_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(get_session_file(dir.path(), "work", "../workspacers").is_err());
+        assert!(get_session_file(dir.path(), "work", "").is_err());
+        assert!(get_session_file(dir.path(), "..", "id-1").is_err());
+        assert!(get_session_file(dir.path(), "a/b", "id-1").is_err());
+    }
+}
@@ -1,52 +1,172 @@
-use config::{Config, File};
-use log::{error, info};
+use config::{Config, Environment, File};
+use log::{LevelFilter, error, info};
 use serde::{Deserialize, Serialize};
 use std::{
+    collections::HashMap,
     io::{Error, ErrorKind},
-    path::PathBuf,
+    path::{Path, PathBuf},
 };
 use toml;
 
-use super::path::get_data_dir;
+use super::path::get_config_dir;
+use crate::icons::IconSet;
+use crate::sort::SortMode;
+use crate::storage::Backend;
 
+// Prefix of the environment variables overriding the config file, e.g. WORKSPACERS_SORT=frecency
+const ENV_PREFIX: &str = "WORKSPACERS";
+
+// Settings shared by workspacers-cli and workspacers-nvim
+// Layered: defaults, then the config file, then WORKSPACERS_* environment variables, then each binary's flags
 #[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
 pub struct AppConfig {
-    pub json_path: String,
+    // Directory holding the lists, the platform's data directory when not set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data_dir: Option<PathBuf>,
+
+    // List used when no list name is given
+    pub default_list: String,
+
+    // Format of new lists, existing lists keep the format of their file: json | toml | sqlite
+    pub storage: Backend,
+
+    // Layout of each workspace row, the built in layout when not set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub display_template: Option<String>,
+
+    // Widest any column is shown, longer values are shortened with an ellipsis
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_column_width: Option<usize>,
+
+    // Icons shown for each workspace: emoji | nerdfont | ascii
+    pub icon_set: IconSet,
+
+    // Icons replacing those of the icon set, by kind (directory, git_repo, file, missing, symlink, url, remote)
+    // or file extension, e.g. icons = { git_repo = "+", rs = "R" }
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub icons: HashMap<String, String>,
+
+    // Order of lists when no sort is given: manual | frecency
+    pub sort: SortMode,
+
+    // Order of particular lists, overriding sort, e.g. list_sort = { notes = "frecency" }
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub list_sort: HashMap<String, SortMode>,
+
+    // Picker used by workspacers-cli: native | fzf
+    pub picker: String,
+
+    // Extra arguments passed to fzf, space separated in WORKSPACERS_FZF_ARGS
+    pub fzf_args: Vec<String>,
+
+    // off | error | warn | info | debug | trace
+    pub log_level: String,
 }
 
-impl AppConfig {
-    pub fn default() -> AppConfig {
+impl Default for AppConfig {
+    fn default() -> AppConfig {
         AppConfig {
-            json_path: "".to_string(),
+            data_dir: None,
+            default_list: "workspacers".to_string(),
+            storage: Backend::default(),
+            display_template: None,
+            max_column_width: None,
+            icon_set: IconSet::default(),
+            icons: HashMap::new(),
+            sort: SortMode::default(),
+            list_sort: HashMap::new(),
+            picker: "native".to_string(),
+            fzf_args: Vec::new(),
+            log_level: "debug".to_string(),
         }
     }
 }
 
+impl AppConfig {
+    // Order of the list when no sort is given for it
+    pub fn sort_for(&self, list_name: &str) -> SortMode {
+        self.list_sort.get(list_name).copied().unwrap_or(self.sort)
+    }
+
+    pub fn log_level_filter(&self) -> Result<LevelFilter, Error> {
+        self.log_level.parse().map_err(|_| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "Invalid log_level: '{}' (expected off, error, warn, info, debug or trace)",
+                    self.log_level
+                ),
+            )
+        })
+    }
+}
+
+// Older versions wrote the config to <data dir>/workspacers/workspacers.toml, next to the lists, where it would be
+// read as the TOML list of the default list. It is moved to the config dir, or aside when a config is already there
+// Best effort, nothing is created and failures are only logged
+fn move_legacy_config(app_name: &str, cfg_file: &Path) {
+    let Some(data_dir) = dirs_next::data_local_dir() else {
+        return;
+    };
+    let legacy_file = data_dir.join(app_name).join(format!("{app_name}.toml"));
+    let is_config = std::fs::read_to_string(&legacy_file)
+        .ok()
+        .and_then(|contents| contents.parse::<toml::Table>().ok())
+        .is_some_and(|table| table.contains_key("json_path") && !table.contains_key("Workspace"));
+    if !is_config {
+        return;
+    }
+
+    let target = match cfg_file.exists() {
+        true => legacy_file.with_extension("toml.old"),
+        false => cfg_file.to_path_buf(),
+    };
+    info!(
+        "Moving old config {} to {}",
+        legacy_file.to_string_lossy(),
+        target.to_string_lossy()
+    );
+    if let Err(err) = std::fs::rename(&legacy_file, &target) {
+        error!("Failed to move old config {}: {err}", legacy_file.to_string_lossy());
+    }
+}
+
+// Names of the WORKSPACERS_* variables set, for errors that could come from either the file or the environment
+fn env_overrides() -> String {
+    let prefix = format!("{ENV_PREFIX}_");
+    let mut names: Vec<String> = std::env::vars_os()
+        .filter_map(|(name, _)| name.into_string().ok())
+        .filter(|name| name.starts_with(&prefix))
+        .collect();
+    names.sort();
+    match names.is_empty() {
+        true => String::new(),
+        false => format!(" or the environment ({})", names.join(", ")),
+    }
+}
+
+// Returns the config file used and the config read from it, the environment and the defaults
+// Without `config_args` the file is <config dir>/workspacers/workspacers.toml, created with the defaults
 pub fn get_config(config_args: Option<PathBuf>, app_name: &str) -> Result<(String, AppConfig), Error> {
     let config_file = match config_args {
         Some(cfg_file) => cfg_file,
         None => {
-            let cfg_file = get_data_dir(app_name)?.join(format!("{app_name}.toml"));
+            let cfg_file = get_config_dir(app_name)?.join(format!("{app_name}.toml"));
+            move_legacy_config(app_name, &cfg_file);
             if !cfg_file.exists() {
-                if let Some(parent) = cfg_file.parent() {
-                    std::fs::create_dir_all(parent).map_err(|err| {
-                        error!("Failed to create directory: {err}");
-                        Error::new(ErrorKind::Other, "Failed to create config directory")
-                    })?;
-                }
-
                 // Create a default config
-                let default_config = AppConfig::default(); // You'll need to implement Default for AppConfig
+                let default_config = AppConfig::default();
 
                 // Write the default config to the file
                 let toml_string = toml::to_string(&default_config).map_err(|err| {
                     error!("Failed to serialize default config: {err}");
-                    Error::new(ErrorKind::Other, "Failed to create default config")
+                    Error::other("Failed to create default config")
                 })?;
 
                 std::fs::write(&cfg_file, toml_string).map_err(|err| {
                     error!("Failed to write default config: {err}");
-                    Error::new(ErrorKind::Other, "Failed to write default config file")
+                    Error::other("Failed to write default config file")
                 })?;
             }
             cfg_file
@@ -55,16 +175,28 @@ pub fn get_config(config_args: Option<PathBuf>, app_name: &str) -> Result<(Strin
     .to_string_lossy()
     .to_string();
 
+    let defaults = Config::try_from(&AppConfig::default()).map_err(|err| {
+        error!("{err}");
+        Error::other("Could not build default config")
+    })?;
+
     Ok((
         config_file.to_string(),
         Config::builder()
+            .add_source(defaults)
             .add_source(File::with_name(&config_file))
+            .add_source(
+                Environment::with_prefix(ENV_PREFIX)
+                    .try_parsing(true)
+                    .list_separator(" ")
+                    .with_list_parse_key("fzf_args"),
+            )
             .build()
             .map_err(|err| {
                 error!("{err}");
                 Error::new(
                     ErrorKind::InvalidData,
-                    format!("Could not build config file: {}", config_file),
+                    format!("Could not build config from {config_file}{}: {err}", env_overrides()),
                 )
             })?
             .try_deserialize()
@@ -72,8 +204,59 @@ pub fn get_config(config_args: Option<PathBuf>, app_name: &str) -> Result<(Strin
                 error!("{err}");
                 Error::new(
                     ErrorKind::InvalidData,
-                    format!("Could not deserialize config file: {}", config_file),
+                    format!("Invalid config in {config_file}{}: {err}", env_overrides()),
                 )
             })?,
     ))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn env_overrides_the_file_and_defaults() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("workspacers.toml");
+        std::fs::write(
+            &file,
+            "default_list = \"work\"\nsort = \"frecency\"\nfzf_args = [\"--exact\"]\n",
+        )
+        .unwrap();
+
+        // Only this test reads the WORKSPACERS_* variables
+        unsafe {
+            std::env::set_var("WORKSPACERS_SORT", "manual");
+            std::env::set_var("WORKSPACERS_FZF_ARGS", "--cycle --no-sort");
+        }
+        let config = get_config(Some(file.clone()), "workspacers");
+        unsafe { std::env::set_var("WORKSPACERS_SORT", "bogus") };
+        let err = get_config(Some(file), "workspacers");
+        unsafe {
+            std::env::remove_var("WORKSPACERS_SORT");
+            std::env::remove_var("WORKSPACERS_FZF_ARGS");
+        }
+
+        let (_, config) = config.unwrap();
+        assert_eq!(config.default_list, "work");
+        assert_eq!(config.sort, SortMode::Manual);
+        assert_eq!(config.fzf_args, ["--cycle", "--no-sort"]);
+        assert_eq!(config.picker, "native");
+        let err = err.unwrap_err().to_string();
+        assert!(
+            err.contains("or the environment (") && err.contains("WORKSPACERS_SORT"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn list_sort_overrides_sort() {
+        let config = AppConfig {
+            sort: SortMode::Manual,
+            list_sort: HashMap::from([("notes".to_string(), SortMode::Frecency)]),
+            ..AppConfig::default()
+        };
+        assert_eq!(config.sort_for("notes"), SortMode::Frecency);
+        assert_eq!(config.sort_for("work"), SortMode::Manual);
+    }
+}
@@ -8,7 +8,7 @@ use simplelog::WriteLogger;
 
 use crate::setup::path;
 
-pub fn setup_logger() -> Result<PathBuf, Error> {
+pub fn setup_logger(level: LevelFilter) -> Result<PathBuf, Error> {
     let binary_name = path::get_binary_name()?;
     let log_file = match dirs_next::cache_dir() {
         Some(path) => path.join(format!("{binary_name}.log")),
@@ -17,13 +17,7 @@ pub fn setup_logger() -> Result<PathBuf, Error> {
         }
     };
 
-    if WriteLogger::init(
-        LevelFilter::Debug,
-        simplelog::Config::default(),
-        std::fs::File::create(&log_file)?,
-    )
-    .is_err()
-    {
+    if WriteLogger::init(level, simplelog::Config::default(), std::fs::File::create(&log_file)?).is_err() {
         return Err(Error::new(
             ErrorKind::ResourceBusy,
             "Could not create logger(is another logger already initialised?)",
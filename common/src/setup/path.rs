@@ -23,8 +23,17 @@ pub fn get_data_dir(app_name: &str) -> Result<PathBuf, Error> {
             }
             Ok(data_dir.join(app_name))
         }
-        None => {
-            return Err(Error::new(ErrorKind::NotFound, "DataLocal directory not found"));
+        None => Err(Error::new(ErrorKind::NotFound, "DataLocal directory not found")),
+    }
+}
+
+pub fn get_config_dir(app_name: &str) -> Result<PathBuf, Error> {
+    match dirs_next::config_dir() {
+        Some(config_dir) => {
+            let app_dir = config_dir.join(app_name);
+            std::fs::create_dir_all(&app_dir)?;
+            Ok(app_dir)
         }
+        None => Err(Error::new(ErrorKind::NotFound, "Config directory not found")),
     }
 }
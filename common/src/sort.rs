@@ -0,0 +1,120 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::json::Workspace;
+
+const HOUR: u64 = 60 * 60;
+const DAY: u64 = 24 * HOUR;
+const WEEK: u64 = 7 * DAY;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortMode {
+    // Order of the list file, set by PROMOTE/DEMOTE
+    #[default]
+    Manual,
+    // Most frequently and recently opened first, ties keep the manual order
+    Frecency,
+}
+
+impl fmt::Display for SortMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SortMode::Manual => write!(f, "manual"),
+            SortMode::Frecency => write!(f, "frecency"),
+        }
+    }
+}
+
+impl FromStr for SortMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "manual" => Ok(SortMode::Manual),
+            "frecency" => Ok(SortMode::Frecency),
+            _ => Err(format!("Unknown sort mode: '{s}' (expected manual or frecency)")),
+        }
+    }
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0)
+}
+
+// Same weighting as zoxide, the open count scaled by how recently it was last opened
+pub fn frecency(ws: &Workspace, now: u64) -> f64 {
+    let Some(last_opened) = ws.last_opened else {
+        return 0.0;
+    };
+    let age = now.saturating_sub(last_opened);
+    let weight = match age {
+        age if age < HOUR => 4.0,
+        age if age < DAY => 2.0,
+        age if age < WEEK => 0.5,
+        _ => 0.25,
+    };
+    ws.open_count as f64 * weight
+}
+
+// Returns the workspaces in display order, the list file order is left untouched
+pub fn sorted(workspaces: &[Workspace], mode: SortMode) -> Vec<Workspace> {
+    let mut sorted = workspaces.to_vec();
+    if mode == SortMode::Frecency {
+        let now = now();
+        // Stable sort so ties keep the manual order
+        sorted.sort_by(|a, b| frecency(b, now).total_cmp(&frecency(a, now)));
+    }
+    sorted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 100 * WEEK;
+
+    fn ws(name: &str, open_count: u32, age: Option<u64>) -> Workspace {
+        let mut ws = Workspace::new(name.to_string(), format!("/src/{name}"));
+        ws.open_count = open_count;
+        ws.last_opened = age.map(|age| NOW - age);
+        ws
+    }
+
+    #[test]
+    fn frecency_weights_by_age() {
+        assert_eq!(frecency(&ws("a", 2, Some(HOUR - 1)), NOW), 8.0);
+        assert_eq!(frecency(&ws("a", 2, Some(HOUR)), NOW), 4.0);
+        assert_eq!(frecency(&ws("a", 2, Some(DAY)), NOW), 1.0);
+        assert_eq!(frecency(&ws("a", 2, Some(WEEK)), NOW), 0.5);
+        assert_eq!(frecency(&ws("a", 2, None), NOW), 0.0);
+        // Opened in the future, such as after a clock change, counts as just opened
+        assert_eq!(frecency(&ws("a", 2, Some(0)), NOW - HOUR), 8.0);
+    }
+
+    #[test]
+    fn ties_keep_the_manual_order() {
+        let workspaces = [
+            ws("never", 0, None),
+            ws("old", 8, Some(2 * WEEK)),
+            ws("recent", 1, Some(0)),
+            ws("also_never", 0, None),
+            ws("also_old", 8, Some(3 * WEEK)),
+        ];
+        let names = |mode| -> Vec<String> { sorted(&workspaces, mode).into_iter().map(|ws| ws.name).collect() };
+        assert_eq!(
+            names(SortMode::Manual),
+            ["never", "old", "recent", "also_never", "also_old"]
+        );
+        assert_eq!(
+            names(SortMode::Frecency),
+            ["old", "also_old", "recent", "never", "also_never"]
+        );
+    }
+}
@@ -0,0 +1,417 @@
+use std::fmt;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+use std::str::FromStr;
+
+use rusqlite::{Connection, OpenFlags, params};
+use serde::{Deserialize, Serialize};
+
+use crate::json::{self, ReadError, SCHEMA_VERSION, Workspace};
+
+// Format of a list file, a list's format is picked by its file's extension
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    // One pretty printed array per list
+    #[default]
+    Json,
+    // A [[Workspace]] table per entry, for editing by hand
+    Toml,
+    // Embedded database, for large lists
+    Sqlite,
+}
+
+impl Backend {
+    // Checked in this order when a list exists in more than one format
+    pub const ALL: [Backend; 3] = [Backend::Json, Backend::Toml, Backend::Sqlite];
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Backend::Json => "json",
+            Backend::Toml => "toml",
+            Backend::Sqlite => "db",
+        }
+    }
+
+    pub fn from_file(file: &Path) -> Option<Backend> {
+        let extension = file.extension()?;
+        Backend::ALL
+            .into_iter()
+            .find(|backend| extension == backend.extension())
+    }
+
+    pub fn storage(self) -> &'static dyn Storage {
+        match self {
+            Backend::Json => &JsonStorage,
+            Backend::Toml => &TomlStorage,
+            Backend::Sqlite => &SqliteStorage,
+        }
+    }
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Backend::Json => write!(f, "json"),
+            Backend::Toml => write!(f, "toml"),
+            Backend::Sqlite => write!(f, "sqlite"),
+        }
+    }
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Backend, String> {
+        match s.to_lowercase().as_str() {
+            "json" => Ok(Backend::Json),
+            "toml" => Ok(Backend::Toml),
+            "sqlite" => Ok(Backend::Sqlite),
+            _ => Err(format!(
+                "Unknown storage backend: '{s}' (expected json, toml or sqlite)"
+            )),
+        }
+    }
+}
+
+// A list as read from its file, `version` is the schema version it was written with
+pub struct StoredList {
+    pub version: u32,
+    pub workspaces: Vec<Workspace>,
+}
+
+// Reads and writes a whole list. Locking, id migration and backups are handled by the caller
+// Lists are always written with the current `SCHEMA_VERSION`
+pub trait Storage {
+    fn read(&self, file: &Path) -> Result<StoredList, ReadError>;
+
+    // Must leave the previous list intact if the write fails
+    fn write(&self, file: &Path, workspaces: &[Workspace]) -> Result<(), Error>;
+
+    // Contents of the file `write` would produce, None for binary formats
+    fn render(&self, workspaces: &[Workspace]) -> Result<Option<String>, Error>;
+}
+
+// Storage for the list file's extension, files with other extensions are read as JSON
+pub fn for_file(file: &Path) -> &'static dyn Storage {
+    Backend::from_file(file).unwrap_or_default().storage()
+}
+
+// Lists written before versioning have no version, they are version 1
+const LEGACY_VERSION: u32 = 1;
+
+fn legacy_version() -> u32 {
+    LEGACY_VERSION
+}
+
+pub struct JsonStorage;
+
+// { "version": 2, "workspaces": [ ... ] }, version 1 lists are a bare array of workspaces
+#[derive(Serialize, Deserialize)]
+struct JsonList<W> {
+    version: u32,
+    workspaces: W,
+}
+
+impl Storage for JsonStorage {
+    fn read(&self, file: &Path) -> Result<StoredList, ReadError> {
+        let contents = std::fs::read_to_string(file).map_err(|err| ReadError::from_io(file, err))?;
+        let parse_err = |err: serde_json::Error| {
+            // serde_json appends the position to its message, it's kept in separate fields here
+            let position = format!(" at line {} column {}", err.line(), err.column());
+            let message = err.to_string();
+            ReadError::Parse {
+                file: file.to_path_buf(),
+                line: err.line(),
+                column: err.column(),
+                message: message.strip_suffix(&position).unwrap_or(&message).to_string(),
+            }
+        };
+        // Checked up front rather than with an untagged enum, which would lose the error positions
+        if contents.trim_start().starts_with('[') {
+            return Ok(StoredList {
+                version: LEGACY_VERSION,
+                workspaces: serde_json::from_str(&contents).map_err(parse_err)?,
+            });
+        }
+        let list: JsonList<Vec<Workspace>> = serde_json::from_str(&contents).map_err(parse_err)?;
+        Ok(StoredList {
+            version: list.version,
+            workspaces: list.workspaces,
+        })
+    }
+
+    fn write(&self, file: &Path, workspaces: &[Workspace]) -> Result<(), Error> {
+        let json = self.render(workspaces)?.unwrap_or_default();
+        json::write_bytes(file, json.as_bytes())
+    }
+
+    fn render(&self, workspaces: &[Workspace]) -> Result<Option<String>, Error> {
+        let list = JsonList {
+            version: SCHEMA_VERSION,
+            workspaces,
+        };
+        serde_json::to_string_pretty(&list).map(Some).map_err(Error::other)
+    }
+}
+
+pub struct TomlStorage;
+
+// TOML can't have an array at the top level
+#[derive(Serialize, Deserialize)]
+struct TomlList {
+    #[serde(default = "legacy_version")]
+    version: u32,
+
+    #[serde(rename = "Workspace", default)]
+    workspaces: Vec<Workspace>,
+}
+
+impl Storage for TomlStorage {
+    fn read(&self, file: &Path) -> Result<StoredList, ReadError> {
+        let contents = std::fs::read_to_string(file).map_err(|err| ReadError::from_io(file, err))?;
+        let list: TomlList = toml::from_str(&contents).map_err(|err| {
+            // toml gives a byte span, converted to the line and column used for JSON
+            let offset = err.span().map(|span| span.start).unwrap_or_default();
+            let before = &contents[..offset.min(contents.len())];
+            let line = before.matches('\n').count() + 1;
+            let column = before.chars().rev().take_while(|c| *c != '\n').count() + 1;
+            ReadError::Parse {
+                file: file.to_path_buf(),
+                line,
+                column,
+                message: err.message().to_string(),
+            }
+        })?;
+        Ok(StoredList {
+            version: list.version,
+            workspaces: list.workspaces,
+        })
+    }
+
+    fn write(&self, file: &Path, workspaces: &[Workspace]) -> Result<(), Error> {
+        let toml = self.render(workspaces)?.unwrap_or_default();
+        json::write_bytes(file, toml.as_bytes())
+    }
+
+    fn render(&self, workspaces: &[Workspace]) -> Result<Option<String>, Error> {
+        let list = TomlList {
+            version: SCHEMA_VERSION,
+            workspaces: workspaces.to_vec(),
+        };
+        toml::to_string_pretty(&list).map(Some).map_err(Error::other)
+    }
+}
+
+pub struct SqliteStorage;
+
+const SQLITE_SCHEMA: &str = "CREATE TABLE IF NOT EXISTS workspaces (
+    position INTEGER NOT NULL,
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    path TEXT NOT NULL,
+    tags TEXT NOT NULL DEFAULT '[]',
+    open_count INTEGER NOT NULL DEFAULT 0,
+    last_opened INTEGER
+)";
+
+impl Storage for SqliteStorage {
+    // The version is kept in the database's user_version, which is 0 until written with a version
+    fn read(&self, file: &Path) -> Result<StoredList, ReadError> {
+        // Opening would otherwise create the database
+        if !file.exists() {
+            return Err(ReadError::NotFound(file.to_path_buf()));
+        }
+        let sql_err = |err: rusqlite::Error| ReadError::Io(file.to_path_buf(), Error::other(err));
+        let conn = Connection::open_with_flags(file, OpenFlags::SQLITE_OPEN_READ_ONLY).map_err(sql_err)?;
+
+        let version: u32 = conn
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .map_err(sql_err)?;
+        let version = version.max(LEGACY_VERSION);
+
+        // A database without the table yet is an empty list
+        let has_table: bool = conn
+            .query_row(
+                "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'workspaces')",
+                [],
+                |row| row.get(0),
+            )
+            .map_err(sql_err)?;
+        if !has_table {
+            return Ok(StoredList {
+                version,
+                workspaces: Vec::new(),
+            });
+        }
+
+        let mut statement = conn
+            .prepare("SELECT id, name, path, tags, open_count, last_opened FROM workspaces ORDER BY position")
+            .map_err(sql_err)?;
+        let rows = statement
+            .query_map([], |row| {
+                let tags: String = row.get(3)?;
+                let ws = Workspace {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    path: row.get(2)?,
+                    tags: Vec::new(),
+                    open_count: row.get(4)?,
+                    last_opened: row.get::<_, Option<i64>>(5)?.map(|time| time as u64),
+                };
+                Ok((ws, tags))
+            })
+            .map_err(sql_err)?;
+        // Tags are a JSON array, a corrupt value is reported rather than dropped
+        let workspaces = rows
+            .map(|row| {
+                let (ws, tags) = row.map_err(sql_err)?;
+                let tags = serde_json::from_str(&tags).map_err(|err| {
+                    let message = format!("Invalid tags of workspace '{}': {err}", ws.id);
+                    ReadError::Io(file.to_path_buf(), Error::new(ErrorKind::InvalidData, message))
+                })?;
+                Ok(Workspace { tags, ..ws })
+            })
+            .collect::<Result<Vec<Workspace>, ReadError>>()?;
+        Ok(StoredList { version, workspaces })
+    }
+
+    // The list is replaced in a single transaction, so a failed write is rolled back
+    fn write(&self, file: &Path, workspaces: &[Workspace]) -> Result<(), Error> {
+        let mut conn = Connection::open(file).map_err(Error::other)?;
+        let tx = conn.transaction().map_err(Error::other)?;
+        tx.execute(SQLITE_SCHEMA, []).map_err(Error::other)?;
+        tx.pragma_update(None, "user_version", SCHEMA_VERSION)
+            .map_err(Error::other)?;
+        tx.execute("DELETE FROM workspaces", []).map_err(Error::other)?;
+        {
+            let mut insert = tx
+                .prepare(
+                    "INSERT INTO workspaces (position, id, name, path, tags, open_count, last_opened)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                )
+                .map_err(Error::other)?;
+            for (position, ws) in workspaces.iter().enumerate() {
+                let tags = serde_json::to_string(&ws.tags).map_err(Error::other)?;
+                insert
+                    .execute(params![
+                        position as i64,
+                        ws.id,
+                        ws.name,
+                        ws.path,
+                        tags,
+                        ws.open_count,
+                        ws.last_opened.map(|time| time as i64),
+                    ])
+                    .map_err(Error::other)?;
+            }
+        }
+        tx.commit().map_err(Error::other)
+    }
+
+    fn render(&self, _workspaces: &[Workspace]) -> Result<Option<String>, Error> {
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn workspaces() -> Vec<Workspace> {
+        let mut tagged = Workspace::new("a".to_string(), "/src/a".to_string());
+        tagged.add_tag("rust");
+        tagged.mark_opened();
+        vec![tagged, Workspace::new("b".to_string(), "/src/b".to_string())]
+    }
+
+    #[test]
+    fn corrupt_sqlite_tags_are_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("list.db");
+        json::write_workspaces(&file, &workspaces()).unwrap();
+        let conn = Connection::open(&file).unwrap();
+        conn.execute("UPDATE workspaces SET tags = 'rust' WHERE name = 'a'", [])
+            .unwrap();
+        assert!(matches!(json::read_workspaces(&file), Err(ReadError::Io(..))));
+    }
+
+    #[test]
+    fn backends_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let workspaces = workspaces();
+        for backend in Backend::ALL {
+            let file = dir.path().join(format!("list.{}", backend.extension()));
+            assert_eq!(Backend::from_file(&file), Some(backend));
+            json::write_workspaces(&file, &workspaces).unwrap();
+            assert_eq!(json::read_workspaces(&file).unwrap(), workspaces, "{backend}");
+        }
+        // The existing json list is used over the configured backend
+        assert_eq!(
+            json::get_list_file(dir.path(), "list", Backend::Sqlite),
+            dir.path().join("list.json")
+        );
+        assert_eq!(json::get_list_names(dir.path()).unwrap(), ["list"]);
+    }
+
+    #[test]
+    fn missing_lists_are_not_found() {
+        let dir = tempfile::tempdir().unwrap();
+        for backend in Backend::ALL {
+            let file = json::get_list_file(dir.path(), "missing", backend);
+            assert!(matches!(json::read_workspaces(&file), Err(ReadError::NotFound(_))));
+            assert!(!file.exists());
+        }
+    }
+
+    #[test]
+    fn toml_parse_error_position() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("list.toml");
+        std::fs::write(&file, "[[Workspace]]\nName = \"a\"\nPath = \n").unwrap();
+        match json::read_workspaces(&file) {
+            Err(ReadError::Parse { line, column, .. }) => assert_eq!((line, column), (3, 8)),
+            result => panic!("expected parse error: {result:?}"),
+        }
+    }
+
+    #[test]
+    fn legacy_json_is_upgraded_on_write() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("list.json");
+        std::fs::write(&file, r#"[{ "Id": "a1", "Name": "a", "Path": "/src/a" }]"#).unwrap();
+        assert_eq!(JsonStorage.read(&file).unwrap().version, 1);
+
+        let workspaces = json::read_workspaces(&file).unwrap();
+        assert_eq!(workspaces[0].id, "a1");
+        json::write_workspaces(&file, &workspaces).unwrap();
+        let list = JsonStorage.read(&file).unwrap();
+        assert_eq!((list.version, list.workspaces), (SCHEMA_VERSION, workspaces));
+    }
+
+    #[test]
+    fn reads_leave_the_upgrade_to_migrate() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("list.json");
+        std::fs::write(&file, r#"[{ "Name": "a", "Path": "/src/a" }]"#).unwrap();
+        crate::store::WorkspaceStore::open(&file).unwrap();
+        json::read_all_workspaces(dir.path()).unwrap();
+
+        assert_eq!(JsonStorage.read(&file).unwrap().version, 1);
+        let migration = json::plan_migration(&file).unwrap();
+        assert!(migration.is_needed());
+        json::apply_migration(&migration).unwrap();
+        assert_eq!(JsonStorage.read(&file).unwrap().version, SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn newer_versions_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("list.json");
+        std::fs::write(&file, r#"{ "version": 99, "workspaces": [] }"#).unwrap();
+        assert!(matches!(
+            json::read_workspaces(&file),
+            Err(ReadError::UnsupportedVersion(_, 99))
+        ));
+    }
+}
@@ -0,0 +1,465 @@
+use std::fmt;
+use std::io::Error;
+use std::path::{Path, PathBuf};
+
+use log::info;
+
+use crate::json::{self, ListLock, ReadError, Workspace};
+
+#[derive(Debug, PartialEq)]
+pub enum ValidationError {
+    EmptyName,
+    DuplicateName(String),
+    DuplicatePath(String),
+    EmptyTag,
+    InvalidTag(String),
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationError::EmptyName => write!(f, "Name cannot be empty"),
+            ValidationError::DuplicateName(name) => write!(f, "Workspace Name already exists: '{name}'"),
+            ValidationError::DuplicatePath(path) => write!(f, "Workspace Path already exists: '{path}'"),
+            ValidationError::EmptyTag => write!(f, "Tag cannot be empty"),
+            ValidationError::InvalidTag(tag) => write!(f, "Tag cannot contain ',': '{tag}'"),
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+#[derive(Debug)]
+pub enum StoreError {
+    Read(ReadError),
+    Lock(PathBuf, Error),
+    Write(PathBuf, Error),
+    // No workspace with the id
+    NotFound(String),
+    Invalid(ValidationError),
+    OutOfRange { index: usize, len: usize },
+    // The change could not be applied because the list was modified by another process since it was read
+    Conflict(Box<StoreError>),
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreError::Read(err) => write!(f, "{err}"),
+            StoreError::Lock(file, err) => write!(f, "Could not lock '{}': {err}", file.to_string_lossy()),
+            StoreError::Write(file, err) => write!(f, "Could not write '{}': {err}", file.to_string_lossy()),
+            StoreError::NotFound(id) => write!(f, "Workspace not found: '{id}'"),
+            StoreError::Invalid(err) => write!(f, "{err}"),
+            StoreError::OutOfRange { index, len } => {
+                write!(f, "Index out of range: {index} (list has {len} workspaces)")
+            }
+            StoreError::Conflict(err) => write!(f, "List was changed by another process: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for StoreError {}
+
+impl From<ValidationError> for StoreError {
+    fn from(err: ValidationError) -> StoreError {
+        StoreError::Invalid(err)
+    }
+}
+
+// A single list and the snapshot of it last read or written
+// Every change is made under the list lock against the latest list, so changes from other processes
+// (workspacers-cli and workspacers-nvim) are kept, and validated the same way for both binaries
+pub struct WorkspaceStore {
+    json_file: PathBuf,
+    workspaces: Vec<Workspace>,
+}
+
+impl WorkspaceStore {
+    // A list that doesn't exist yet is empty and is created on the first change
+    pub fn open(json_file: &Path) -> Result<WorkspaceStore, ReadError> {
+        Ok(WorkspaceStore {
+            json_file: json_file.to_path_buf(),
+            workspaces: json::read_workspaces_or_empty(json_file)?,
+        })
+    }
+
+    pub fn json_file(&self) -> &Path {
+        &self.json_file
+    }
+
+    pub fn workspaces(&self) -> &[Workspace] {
+        &self.workspaces
+    }
+
+    // Picks up changes made by other processes
+    pub fn reload(&mut self) -> Result<(), ReadError> {
+        self.workspaces = json::read_workspaces_or_empty(&self.json_file)?;
+        Ok(())
+    }
+
+    pub fn find_by_id(&self, id: &str) -> Option<&Workspace> {
+        self.workspaces.iter().find(|ws| ws.id == id)
+    }
+
+    pub fn find_by_name(&self, name: &str) -> Option<&Workspace> {
+        self.workspaces.iter().find(|ws| ws.name == name)
+    }
+
+    pub fn find_by_path(&self, path: &str) -> Option<&Workspace> {
+        self.workspaces.iter().find(|ws| ws.path == path)
+    }
+
+    pub fn add(&mut self, ws: Workspace) -> Result<(), StoreError> {
+        info!("Adding: {ws} to: {}", self.json_file.to_string_lossy());
+        self.apply(|latest| {
+            validate_workspace(latest, &ws)?;
+            latest.push(ws);
+            Ok(())
+        })
+    }
+
+    // Returns the removed workspace
+    pub fn remove(&mut self, id: &str) -> Result<Workspace, StoreError> {
+        self.apply(|latest| {
+            let idx = position(latest, id)?;
+            Ok(latest.remove(idx))
+        })
+    }
+
+    // Changes the workspace in place, the id is kept so it can still be addressed after an edit
+    // Returns the updated workspace
+    pub fn update(&mut self, id: &str, change: impl FnOnce(&mut Workspace)) -> Result<Workspace, StoreError> {
+        self.apply(|latest| {
+            let idx = position(latest, id)?;
+            let mut updated = latest[idx].clone();
+            change(&mut updated);
+            updated.id = id.to_string();
+            validate_workspace(latest, &updated)?;
+            latest[idx] = updated.clone();
+            Ok(updated)
+        })
+    }
+
+    // Moves a workspace to an index in the list, shifting the others along
+    pub fn move_to(&mut self, id: &str, index: usize) -> Result<(), StoreError> {
+        self.apply(|latest| {
+            let idx = position(latest, id)?;
+            if index >= latest.len() {
+                return Err(StoreError::OutOfRange {
+                    index,
+                    len: latest.len(),
+                });
+            }
+            let ws = latest.remove(idx);
+            latest.insert(index, ws);
+            Ok(())
+        })
+    }
+
+    // Swaps the places of two workspaces in the list
+    pub fn swap(&mut self, id: &str, other_id: &str) -> Result<(), StoreError> {
+        self.apply(|latest| {
+            let idx = position(latest, id)?;
+            let other_idx = position(latest, other_id)?;
+            latest.swap(idx, other_idx);
+            Ok(())
+        })
+    }
+
+    // Moves a workspace up one place, the first wraps round to the end. Returns the new index
+    pub fn promote(&mut self, id: &str) -> Result<usize, StoreError> {
+        self.apply(|latest| {
+            let idx = position(latest, id)?;
+            let target_idx = if idx == 0 { latest.len() - 1 } else { idx - 1 };
+            let ws = latest.remove(idx);
+            latest.insert(target_idx, ws);
+            Ok(target_idx)
+        })
+    }
+
+    // Moves a workspace down one place, the last wraps round to the start. Returns the new index
+    pub fn demote(&mut self, id: &str) -> Result<usize, StoreError> {
+        self.apply(|latest| {
+            let idx = position(latest, id)?;
+            let target_idx = if idx == latest.len() - 1 { 0 } else { idx + 1 };
+            let ws = latest.remove(idx);
+            latest.insert(target_idx, ws);
+            Ok(target_idx)
+        })
+    }
+
+    // Marks a workspace as opened, used for frecency sorting
+    pub fn record_opened(&mut self, id: &str) -> Result<(), StoreError> {
+        self.apply(|latest| {
+            let idx = position(latest, id)?;
+            latest[idx].mark_opened();
+            Ok(())
+        })
+    }
+
+    // Returns the workspace's tags
+    pub fn add_tag(&mut self, id: &str, tag: &str) -> Result<Vec<String>, StoreError> {
+        let tag = validate_tag(tag)?;
+        self.apply(|latest| {
+            let idx = position(latest, id)?;
+            latest[idx].add_tag(&tag);
+            Ok(latest[idx].tags.clone())
+        })
+    }
+
+    // Returns the workspace's tags
+    pub fn remove_tag(&mut self, id: &str, tag: &str) -> Result<Vec<String>, StoreError> {
+        let tag = validate_tag(tag)?;
+        self.apply(|latest| {
+            let idx = position(latest, id)?;
+            latest[idx].remove_tag(&tag);
+            Ok(latest[idx].tags.clone())
+        })
+    }
+
+    // Applies a change to the latest list under the list lock, Err from `change` aborts the write
+    // If the list changed since it was last read the change is applied to the latest list
+    fn apply<T>(&mut self, change: impl FnOnce(&mut Vec<Workspace>) -> Result<T, StoreError>) -> Result<T, StoreError> {
+        let _lock = ListLock::acquire(&self.json_file).map_err(|err| StoreError::Lock(self.json_file.clone(), err))?;
+        let latest = json::read_workspaces_or_empty(&self.json_file).map_err(StoreError::Read)?;
+        let modified = latest != self.workspaces;
+        if modified {
+            info!(
+                "List modified since read, merging into latest: {}",
+                self.json_file.to_string_lossy()
+            );
+        }
+
+        let mut changed = latest.clone();
+        let result = change(&mut changed);
+        let result = match result {
+            Ok(result) => result,
+            Err(err) => {
+                self.workspaces = latest;
+                return Err(match modified {
+                    true => StoreError::Conflict(Box::new(err)),
+                    false => err,
+                });
+            }
+        };
+        let written = match usage_only(&latest, &changed) {
+            true => json::write_usage(&self.json_file, &changed),
+            false => json::write_workspaces(&self.json_file, &changed),
+        };
+        written.map_err(|err| StoreError::Write(self.json_file.clone(), err))?;
+        self.workspaces = changed;
+        Ok(result)
+    }
+}
+
+// Only the open counts and times differ, so the change isn't worth a backup
+fn usage_only(before: &[Workspace], after: &[Workspace]) -> bool {
+    let without_usage = |ws: &Workspace| Workspace {
+        open_count: 0,
+        last_opened: None,
+        ..ws.clone()
+    };
+    before.len() == after.len()
+        && before
+            .iter()
+            .zip(after)
+            .all(|(before, after)| without_usage(before) == without_usage(after))
+}
+
+fn position(workspaces: &[Workspace], id: &str) -> Result<usize, StoreError> {
+    json::position_by_id(workspaces, id).ok_or_else(|| StoreError::NotFound(id.to_string()))
+}
+
+// Name must be set and the name and path unique among the other workspaces
+pub fn validate_workspace(workspaces: &[Workspace], ws: &Workspace) -> Result<(), ValidationError> {
+    if ws.name.is_empty() {
+        return Err(ValidationError::EmptyName);
+    }
+    let others = || workspaces.iter().filter(|other| other.id != ws.id);
+    if others().any(|other| other.name == ws.name) {
+        return Err(ValidationError::DuplicateName(ws.name.to_string()));
+    }
+    if others().any(|other| other.path == ws.path) {
+        return Err(ValidationError::DuplicatePath(ws.path.to_string()));
+    }
+    Ok(())
+}
+
+// Tags are trimmed and can't be empty or contain the ", " used to display them
+pub fn validate_tag(tag: &str) -> Result<String, ValidationError> {
+    let tag = tag.trim();
+    if tag.is_empty() {
+        return Err(ValidationError::EmptyTag);
+    }
+    if tag.contains(',') {
+        return Err(ValidationError::InvalidTag(tag.to_string()));
+    }
+    Ok(tag.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::Backend;
+
+    fn store_with(names: &[&str]) -> (tempfile::TempDir, WorkspaceStore) {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = WorkspaceStore::open(&json::get_list_file(dir.path(), "test", Backend::default())).unwrap();
+        for name in names {
+            store
+                .add(Workspace::new(name.to_string(), format!("/src/{name}")))
+                .unwrap();
+        }
+        (dir, store)
+    }
+
+    fn names(store: &WorkspaceStore) -> Vec<&str> {
+        store.workspaces().iter().map(|ws| ws.name.as_str()).collect()
+    }
+
+    fn id(store: &WorkspaceStore, name: &str) -> String {
+        store.find_by_name(name).unwrap().id.to_string()
+    }
+
+    #[test]
+    fn open_missing_list_is_empty() {
+        let (_dir, store) = store_with(&[]);
+        assert!(store.workspaces().is_empty());
+        assert!(!store.json_file().exists());
+    }
+
+    #[test]
+    fn add_writes_the_list() {
+        let (_dir, store) = store_with(&["a", "b"]);
+        assert_eq!(names(&store), ["a", "b"]);
+        let reopened = WorkspaceStore::open(store.json_file()).unwrap();
+        assert_eq!(reopened.workspaces(), store.workspaces());
+    }
+
+    #[test]
+    fn add_rejects_empty_and_duplicates() {
+        let (_dir, mut store) = store_with(&["a"]);
+        let err = store.add(Workspace::new(String::new(), "/other".to_string()));
+        assert!(matches!(err, Err(StoreError::Invalid(ValidationError::EmptyName))));
+        let err = store.add(Workspace::new("a".to_string(), "/other".to_string()));
+        assert!(matches!(
+            err,
+            Err(StoreError::Invalid(ValidationError::DuplicateName(_)))
+        ));
+        let err = store.add(Workspace::new("b".to_string(), "/src/a".to_string()));
+        assert!(matches!(
+            err,
+            Err(StoreError::Invalid(ValidationError::DuplicatePath(_)))
+        ));
+        assert_eq!(names(&store), ["a"]);
+    }
+
+    #[test]
+    fn remove_returns_the_workspace() {
+        let (_dir, mut store) = store_with(&["a", "b"]);
+        let removed = store.remove(&id(&store, "a")).unwrap();
+        assert_eq!(removed.name, "a");
+        assert_eq!(names(&store), ["b"]);
+        assert!(matches!(store.remove(&removed.id), Err(StoreError::NotFound(_))));
+    }
+
+    #[test]
+    fn update_keeps_id_and_validates() {
+        let (_dir, mut store) = store_with(&["a", "b"]);
+        let a_id = id(&store, "a");
+        let updated = store
+            .update(&a_id, |ws| {
+                ws.name = "c".to_string();
+                ws.id = "changed".to_string();
+            })
+            .unwrap();
+        assert_eq!(updated.id, a_id);
+        assert_eq!(store.find_by_id(&a_id).unwrap().name, "c");
+
+        let err = store.update(&a_id, |ws| ws.path = "/src/b".to_string());
+        assert!(matches!(
+            err,
+            Err(StoreError::Invalid(ValidationError::DuplicatePath(_)))
+        ));
+        assert!(store.find_by_path("/src/a").is_some());
+    }
+
+    #[test]
+    fn move_to_and_swap() {
+        let (_dir, mut store) = store_with(&["a", "b", "c"]);
+        store.move_to(&id(&store, "a"), 2).unwrap();
+        assert_eq!(names(&store), ["b", "c", "a"]);
+        let err = store.move_to(&id(&store, "a"), 3);
+        assert!(matches!(err, Err(StoreError::OutOfRange { index: 3, len: 3 })));
+        store.swap(&id(&store, "b"), &id(&store, "a")).unwrap();
+        assert_eq!(names(&store), ["a", "c", "b"]);
+    }
+
+    #[test]
+    fn promote_and_demote_wrap() {
+        let (_dir, mut store) = store_with(&["a", "b", "c"]);
+        assert_eq!(store.promote(&id(&store, "a")).unwrap(), 2);
+        assert_eq!(names(&store), ["b", "c", "a"]);
+        assert_eq!(store.demote(&id(&store, "a")).unwrap(), 0);
+        assert_eq!(names(&store), ["a", "b", "c"]);
+        assert_eq!(store.demote(&id(&store, "a")).unwrap(), 1);
+        assert_eq!(names(&store), ["b", "a", "c"]);
+    }
+
+    #[test]
+    fn tags_are_validated() {
+        let (_dir, mut store) = store_with(&["a"]);
+        let a_id = id(&store, "a");
+        assert_eq!(store.add_tag(&a_id, " rust ").unwrap(), ["rust"]);
+        assert_eq!(store.add_tag(&a_id, "rust").unwrap(), ["rust"]);
+        assert!(matches!(
+            store.add_tag(&a_id, "a,b"),
+            Err(StoreError::Invalid(ValidationError::InvalidTag(_)))
+        ));
+        assert!(store.remove_tag(&a_id, "rust").unwrap().is_empty());
+    }
+
+    #[test]
+    fn record_opened_counts() {
+        let (_dir, mut store) = store_with(&["a"]);
+        let a_id = id(&store, "a");
+        store.record_opened(&a_id).unwrap();
+        store.record_opened(&a_id).unwrap();
+        let ws = store.find_by_id(&a_id).unwrap();
+        assert_eq!(ws.open_count, 2);
+        assert!(ws.last_opened.is_some());
+    }
+
+    #[test]
+    fn opens_are_not_backed_up() {
+        let (_dir, mut store) = store_with(&["a"]);
+        let backup = json::get_backup_file(store.json_file(), 1);
+        let before = std::fs::read_to_string(&backup).ok();
+        store.record_opened(&id(&store, "a")).unwrap();
+        assert_eq!(std::fs::read_to_string(&backup).ok(), before);
+
+        store
+            .add(Workspace::new("b".to_string(), "/src/b".to_string()))
+            .unwrap();
+        assert_ne!(std::fs::read_to_string(&backup).ok(), before);
+    }
+
+    #[test]
+    fn changes_merge_with_other_stores() {
+        let (_dir, mut store) = store_with(&["a"]);
+        let mut other = WorkspaceStore::open(store.json_file()).unwrap();
+        other
+            .add(Workspace::new("b".to_string(), "/src/b".to_string()))
+            .unwrap();
+        store
+            .add(Workspace::new("c".to_string(), "/src/c".to_string()))
+            .unwrap();
+        assert_eq!(names(&store), ["a", "b", "c"]);
+
+        // Already removed by the other store
+        let b_id = id(&store, "b");
+        other.remove(&b_id).unwrap();
+        assert!(matches!(store.remove(&b_id), Err(StoreError::Conflict(_))));
+        assert_eq!(names(&store), ["a", "c"]);
+    }
+}
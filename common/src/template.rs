@@ -0,0 +1,268 @@
+use std::fmt;
+
+use crate::icons::Icons;
+use crate::json::Workspace;
+use crate::setup::config::AppConfig;
+
+// Row layout used when no display_template is configured
+pub const DEFAULT_TEMPLATE: &str = "[ {icon} ] - [ {name:<} ] - [ {path:<} ]";
+// Added to the default layout when any of the shown workspaces has tags
+pub const DEFAULT_TAGS_COLUMN: &str = " - [ {tags} ]";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Field {
+    Icon,
+    Name,
+    Path,
+    Tags,
+    LastOpened,
+    GitBranch,
+    // Position of the row, starting at 1
+    Index,
+}
+
+impl Field {
+    const ALL: [(Field, &str); 7] = [
+        (Field::Icon, "icon"),
+        (Field::Name, "name"),
+        (Field::Path, "path"),
+        (Field::Tags, "tags"),
+        (Field::LastOpened, "last_opened"),
+        (Field::GitBranch, "git_branch"),
+        (Field::Index, "index"),
+    ];
+
+    fn parse(name: &str) -> Option<Field> {
+        Field::ALL.iter().find(|(_, n)| *n == name).map(|(field, _)| *field)
+    }
+
+    // Placeholder name, as used in templates
+    pub fn name(&self) -> &'static str {
+        Field::ALL
+            .iter()
+            .find(|(field, _)| field == self)
+            .map(|(_, n)| *n)
+            .unwrap_or_default()
+    }
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Align {
+    Left,
+    Right,
+    Center,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Width {
+    // Value as is
+    None,
+    // Padded to the widest value in the column
+    Auto,
+    // Padded to at least this many columns
+    Fixed(usize),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Column {
+    pub field: Field,
+    pub align: Align,
+    pub width: Width,
+    // Longer values are truncated with an ellipsis
+    pub max: Option<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Part {
+    Text(String),
+    Column(Column),
+}
+
+// A row layout such as "[ {icon} ] {name:<20} {path:<.40} {tags}"
+// Columns are {field:[align][width][.max]}: align is < (default), > or ^, a width pads the value, an align
+// without a width pads to the widest value, and .max truncates. {{ and }} are literal braces
+// Widths are display widths, so wide chars such as CJK and emoji count as two
+#[derive(Clone, Debug, PartialEq)]
+pub struct Template {
+    pub parts: Vec<Part>,
+    // Append DEFAULT_TAGS_COLUMN when any workspace has tags, only set for the default layout
+    pub tags_when_present: bool,
+    // Max width of columns without their own .max
+    pub max_width: Option<usize>,
+    pub icons: Icons,
+}
+
+impl Default for Template {
+    fn default() -> Template {
+        Template {
+            tags_when_present: true,
+            ..Template::parse(DEFAULT_TEMPLATE).expect("default template is valid")
+        }
+    }
+}
+
+impl Template {
+    pub fn parse(template: &str) -> Result<Template, String> {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let mut column = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => column.push(c),
+                            None => return Err(format!("Unclosed '{{' in template: '{template}'")),
+                        }
+                    }
+                    if !text.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut text)));
+                    }
+                    parts.push(Part::Column(parse_column(&column)?));
+                }
+                '}' => {
+                    return Err(format!(
+                        "Unmatched '}}' in template, use '}}}}' for a brace: '{template}'"
+                    ));
+                }
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+        Ok(Template {
+            parts,
+            tags_when_present: false,
+            max_width: None,
+            icons: Icons::default(),
+        })
+    }
+
+    // The configured display_template, or the default layout when not set, with the configured widths and icons
+    pub fn from_config(config: &AppConfig) -> Result<Template, String> {
+        let template = match config.display_template.as_deref() {
+            Some(template) => Template::parse(template).map_err(|err| format!("Invalid display_template: {err}"))?,
+            None => Template::default(),
+        };
+        Ok(Template {
+            max_width: config.max_column_width,
+            icons: Icons {
+                set: config.icon_set,
+                overrides: config.icons.clone(),
+            },
+            ..template
+        })
+    }
+
+    // The parts to render for `workspaces`
+    pub fn parts_for(&self, workspaces: &[Workspace]) -> Vec<Part> {
+        let mut parts = self.parts.clone();
+        if self.tags_when_present && workspaces.iter().any(|ws| !ws.tags.is_empty()) {
+            parts.extend(
+                Template::parse(DEFAULT_TAGS_COLUMN)
+                    .expect("tags column is valid")
+                    .parts,
+            );
+        }
+        parts
+    }
+}
+
+// "name", "name:<", "path:>30", "path:.40", "name:^20.20"
+fn parse_column(column: &str) -> Result<Column, String> {
+    let (name, spec) = column.split_once(':').unwrap_or((column, ""));
+    let field = Field::parse(name.trim()).ok_or_else(|| {
+        let names: Vec<&str> = Field::ALL.iter().map(|(_, n)| *n).collect();
+        format!("Unknown template field: '{name}' (expected {})", names.join(", "))
+    })?;
+
+    let mut spec = spec.chars().peekable();
+    let align = match spec.peek() {
+        Some('<') => Some(Align::Left),
+        Some('>') => Some(Align::Right),
+        Some('^') => Some(Align::Center),
+        _ => None,
+    };
+    if align.is_some() {
+        spec.next();
+    }
+    let rest: String = spec.collect();
+    let (width, max) = rest.split_once('.').unwrap_or((&rest, ""));
+    let number = |value: &str| {
+        value
+            .parse::<usize>()
+            .map_err(|_| format!("Invalid width in template field: '{{{column}}}'"))
+    };
+
+    Ok(Column {
+        field,
+        align: align.unwrap_or(Align::Left),
+        width: match (width.is_empty(), align) {
+            (false, _) => Width::Fixed(number(width)?),
+            (true, Some(_)) => Width::Auto,
+            (true, None) => Width::None,
+        },
+        max: match max.is_empty() {
+            true => None,
+            false => Some(number(max)?),
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn malformed_templates_are_errors() {
+        let err = Template::parse("[ {nmae} ]").unwrap_err();
+        assert!(
+            err.starts_with("Unknown template field: 'nmae' (expected icon, name"),
+            "{err}"
+        );
+
+        let err = Template::parse("[ {name ]").unwrap_err();
+        assert_eq!(err, "Unclosed '{' in template: '[ {name ]'");
+
+        let err = Template::parse("name }").unwrap_err();
+        assert!(err.starts_with("Unmatched '}' in template"), "{err}");
+
+        let err = Template::parse("{path:<wide}").unwrap_err();
+        assert_eq!(err, "Invalid width in template field: '{path:<wide}'");
+    }
+
+    #[test]
+    fn doubled_braces_are_text() {
+        let template = Template::parse("{{{name:>4.8}}}").unwrap();
+        assert_eq!(
+            template.parts,
+            vec![
+                Part::Text("{".to_string()),
+                Part::Column(Column {
+                    field: Field::Name,
+                    align: Align::Right,
+                    width: Width::Fixed(4),
+                    max: Some(8),
+                }),
+                Part::Text("}".to_string()),
+            ]
+        );
+    }
+}
@@ -4,6 +4,32 @@ local rpc = require('workspacers.rpc_handler')
 local tele = require('workspacers.telescope')
 local icons = require('utils.icons')
 
+local rpc_names = {
+    list = 'WORKSPACERS.LIST',
+    list_all = 'WORKSPACERS.LIST_ALL',
+    add = 'WORKSPACERS.ADD',
+    delete = 'WORKSPACERS.DELETE',
+    json = 'WORKSPACERS.JSON',
+    promote = 'WORKSPACERS.PROMOTE',
+    demote = 'WORKSPACERS.DEMOTE',
+    record = 'WORKSPACERS.RECORD',
+    replace = 'WORKSPACERS.REPLACE',
+    opened = 'WORKSPACERS.OPENED',
+    add_tag = 'WORKSPACERS.ADD_TAG',
+    remove_tag = 'WORKSPACERS.REMOVE_TAG',
+    config = 'WORKSPACERS.CONFIG',
+    hello = 'WORKSPACERS.HELLO',
+    current = 'WORKSPACERS.CURRENT',
+    save_session = 'WORKSPACERS.SAVE_SESSION',
+    load_session = 'WORKSPACERS.LOAD_SESSION',
+}
+
+-- Settings from the config file: { DefaultList = "workspacers", Sort = "manual", ListSort = { notes = "frecency" } }
+M.LoadConfig = function()
+    M.config = rpc.req(rpc_names.config)
+    return M.config
+end
+
 M.setup = function(opts)
     M.opts = opts or {}
     M.opts.keys = opts.keys or {
@@ -14,20 +40,60 @@ M.setup = function(opts)
         ["<C-a>"] = function(o)
             M.WorkspacersAdd(o)
         end,
+        ["<C-t>"] = function(o) M.TagWorkspace(o, true) end,
+        ["<C-g>"] = function(o) M.TagWorkspace(o, false) end,
     }
+    tele.setup_highlights()
+    local methods = {}
+    for _, method in pairs(rpc_names) do
+        table.insert(methods, method)
+    end
+    rpc.check_version(methods)
+    local group = vim.api.nvim_create_augroup('Workspacers', { clear = true })
+    vim.api.nvim_create_autocmd('User', {
+        group = group,
+        pattern = 'WorkspacersChanged',
+        callback = function(ev) M.OnChanged(ev.data) end,
+    })
+    vim.api.nvim_create_autocmd({ 'VimEnter', 'DirChanged' }, {
+        group = group,
+        callback = function() M.UpdateCurrent() end,
+    })
+    vim.api.nvim_create_autocmd('VimLeavePre', {
+        group = group,
+        callback = function() M.SaveSession() end,
+    })
+    if vim.v.vim_did_enter == 1 then
+        M.UpdateCurrent()
+    end
 end
 
-local rpc_names = {
-    list = 'WORKSPACERS.LIST',
-    list_all = 'WORKSPACERS.LIST_ALL',
-    add = 'WORKSPACERS.ADD',
-    delete = 'WORKSPACERS.DELETE',
-    json = 'WORKSPACERS.JSON',
-    promote = 'WORKSPACERS.PROMOTE',
-    demote = 'WORKSPACERS.DEMOTE',
-    record = 'WORKSPACERS.RECORD',
-    replace = 'WORKSPACERS.REPLACE',
-}
+-- Workspace containing `path` with the list it is in ({ Id, Name, Path, Tags, List }), nil when there is none
+M.Current = function(path)
+    local ws = rpc.req(rpc_names.current, path)
+    if ws == vim.NIL then
+        return nil
+    end
+    return ws
+end
+
+local function fire(pattern, ws)
+    vim.api.nvim_exec_autocmds('User', { pattern = pattern, modeline = false, data = ws })
+end
+
+-- Looks up the workspace of the cwd, firing `User WorkspacersLeave` and `User WorkspacersEnter`
+-- with the workspace as data when it changes
+M.UpdateCurrent = function()
+    local ws = M.Current(vim.fn.getcwd())
+    local old = M.current
+    if (old and old.Id) == (ws and ws.Id) then
+        M.current = ws
+        return
+    end
+    M.current = ws
+    if old then fire('WorkspacersLeave', old) end
+    if ws then fire('WorkspacersEnter', ws) end
+end
 
 local function try_get_input(input_opts, allow_blank)
     local success, input = pcall(function() return vim.fn.input(input_opts) end)
@@ -47,6 +113,41 @@ local function try_get_input(input_opts, allow_blank)
     return input, true
 end
 
+-- Workspace behind the selected Telescope entry, entries are indexed in list order
+local function selected_ws(opts)
+    if opts.selected and opts.selected.index and opts.workspaces then
+        return opts.workspaces[opts.selected.index]
+    end
+    return nil
+end
+
+-- List a workspace belongs to, entries from LIST_ALL carry their own list name
+local function list_name(opts, ws)
+    return (ws and ws.List) or opts.ws_name
+end
+
+-- Sort mode of a list, opts.sort is either a mode for every list or a table of modes by list name
+-- Lists without a mode use the sort of the list from the config file
+local function sort_mode(opts, list)
+    local list_sort = M.config and M.config.ListSort
+    local default = (type(list_sort) == "table" and list_sort[list]) or (M.config and M.config.Sort) or "manual"
+    if type(opts.sort) == "table" then
+        return opts.sort[list] or default
+    end
+    return opts.sort or default
+end
+
+-- Index of the first entry of a list within the picker, lists from LIST_ALL are contiguous
+local function list_offset(opts, ws)
+    if not ws.List then return 0 end
+    for idx, entry in ipairs(opts.workspaces) do
+        if entry.List == ws.List then
+            return idx - 1
+        end
+    end
+    return 0
+end
+
 local function get_buf_path()
     vim.print(vim.fn.expand('%:p'))
     local path = vim.fn.expand('%:p')
@@ -58,7 +159,16 @@ local function get_buf_path()
 end
 
 M.WorkspacersAdd = function(opts)
+    local target_list = list_name(opts, selected_ws(opts))
     opts.close()
+    if not target_list then
+        local input, success = try_get_input({
+            prompt = "Enter List Name: "
+        })
+        if not success then return end
+        target_list = input
+    end
+
     local name, success = try_get_input({
         prompt = "Enter Workspace Name: "
     })
@@ -78,11 +188,16 @@ M.WorkspacersAdd = function(opts)
 
     rpc.req_res(rpc_names.add, function(ws)
         M.WorkspacersList(opts)
-    end, opts.ws_name, new_ws)
+    end, target_list, new_ws)
     M.WorkspacersList(opts)
 end
 
 M.EditWorkspace = function(opts)
+    local selected = selected_ws(opts)
+    if not selected then
+        vim.notify("No selected Workspace", vim.log.levels.ERROR)
+        return
+    end
     rpc.req_res(rpc_names.record, function(ws)
         local name, success = try_get_input({
             prompt = "Enter New Name: ",
@@ -96,58 +211,191 @@ M.EditWorkspace = function(opts)
         })
         if not success then return end
         local rpc_args = {
-            Key = opts.selected[1],
+            Key = ws.Id,
             New = {
                 Name = name,
                 Path = path,
             }
         }
 
-        if opts.selected and opts.selected[1] then
-            rpc.req(rpc_names.replace, opts.ws_name, rpc_args)
-            opts.close()
-            M.WorkspacersList(opts)
-        else
-            vim.notify("No selected Workspace", vim.log.levels.ERROR)
-        end
-    end, opts.ws_name, opts.selected[1])
+        rpc.req(rpc_names.replace, list_name(opts, selected), rpc_args)
+        opts.close()
+        M.WorkspacersList(opts)
+    end, list_name(opts, selected), selected.Id)
 end
 
 M.DeleteWorkspace = function(opts)
     if vim.fn.confirm("Delete Selected Workspace: ", "&Yes\n&No", 2) == 1 then
-        if opts.selected and opts.selected[1] then
+        local selected = selected_ws(opts)
+        if selected then
             rpc.req_res(rpc_names.delete, function()
                 opts.close()
                 M.WorkspacersList(opts)
-            end, opts.ws_name, opts.selected[1])
+            end, list_name(opts, selected), selected.Id)
         else
             vim.notify("No selected Workspace", vim.log.levels.ERROR)
         end
     end
 end
 
-local function select_workspace(ws)
+-- Windows of the tab page from winlayout(), in the shape of the Layout of a session
+local function capture_layout(layout)
+    local kind, value = layout[1], layout[2]
+    if kind == 'leaf' then
+        local buf = vim.api.nvim_win_get_buf(value)
+        local cursor = vim.api.nvim_win_get_cursor(value)
+        return {
+            Leaf = {
+                Path = vim.bo[buf].buftype == '' and vim.api.nvim_buf_get_name(buf) or '',
+                Line = cursor[1],
+                Col = cursor[2],
+                Width = vim.api.nvim_win_get_width(value),
+                Height = vim.api.nvim_win_get_height(value),
+                Current = value == vim.api.nvim_get_current_win(),
+            }
+        }
+    end
+    local children = {}
+    for _, child in ipairs(value) do
+        table.insert(children, capture_layout(child))
+    end
+    return { [kind == 'row' and 'Row' or 'Col'] = children }
+end
+
+local function capture_session()
+    local buffers = {}
+    for _, buf in ipairs(vim.api.nvim_list_bufs()) do
+        local name = vim.api.nvim_buf_get_name(buf)
+        if vim.bo[buf].buflisted and vim.bo[buf].buftype == '' and name ~= '' then
+            table.insert(buffers, name)
+        end
+    end
+    return {
+        Cwd = vim.fn.getcwd(),
+        Buffers = buffers,
+        Layout = capture_layout(vim.fn.winlayout()),
+    }
+end
+
+-- Splits `win` into the windows of `layout`, collecting each window with its saved state in `wins`
+local function restore_layout(layout, win, wins)
+    vim.api.nvim_set_current_win(win)
+    if layout.Leaf then
+        local leaf = layout.Leaf
+        if leaf.Path ~= '' and vim.uv.fs_stat(leaf.Path) then
+            vim.cmd("edit " .. vim.fn.fnameescape(leaf.Path))
+            pcall(vim.api.nvim_win_set_cursor, win, { leaf.Line, leaf.Col })
+        end
+        table.insert(wins, { win = win, leaf = leaf })
+        return
+    end
+    local children = layout.Row or layout.Col
+    local split = layout.Row and "rightbelow vsplit" or "rightbelow split"
+    local child_wins = { win }
+    for _ = 2, #children do
+        vim.cmd(split)
+        table.insert(child_wins, vim.api.nvim_get_current_win())
+    end
+    for idx, child in ipairs(children) do
+        restore_layout(child, child_wins[idx], wins)
+    end
+end
+
+-- Closes the windows and the unmodified buffers under the path of the workspace being left,
+-- buffers of other directories and unsaved changes are kept. No buffers are closed without a workspace being left
+local function clear_session(old_path)
+    vim.cmd("silent! only")
+    if not old_path then return end
+    local root = vim.fs.normalize(vim.fn.fnamemodify(old_path, ":p")):gsub("/$", "")
+    for _, buf in ipairs(vim.api.nvim_list_bufs()) do
+        local name = vim.fs.normalize(vim.api.nvim_buf_get_name(buf))
+        local under_root = name == root or name:sub(1, #root + 1) == root .. "/"
+        if under_root and vim.bo[buf].buflisted and not vim.bo[buf].modified then
+            pcall(vim.api.nvim_buf_delete, buf, {})
+        end
+    end
+end
+
+local function restore_session(session, old_path)
+    clear_session(old_path)
+    vim.cmd("cd " .. vim.fn.fnameescape(session.Cwd))
+    for _, path in ipairs(session.Buffers or {}) do
+        vim.cmd("badd " .. vim.fn.fnameescape(path))
+    end
+    local wins = {}
+    restore_layout(session.Layout, vim.api.nvim_get_current_win(), wins)
+    local current
+    for _, entry in ipairs(wins) do
+        pcall(vim.api.nvim_win_set_width, entry.win, entry.leaf.Width)
+        pcall(vim.api.nvim_win_set_height, entry.win, entry.leaf.Height)
+        if entry.leaf.Current then current = entry.win end
+    end
+    if current then
+        vim.api.nvim_set_current_win(current)
+    end
+end
+
+-- Saves the buffers, windows and cwd of the current workspace, restored when it is next selected
+M.SaveSession = function()
+    if M.opts.sessions ~= true or not M.current then return end
+    rpc.req(rpc_names.save_session, M.current.List, M.current.Id, capture_session())
+end
+
+local function select_workspace(opts, ws)
+    local list = list_name(opts, ws)
+    rpc.req(rpc_names.opened, list, ws.Id)
+    local leaving = M.current and (M.current.Id ~= ws.Id or M.current.List ~= list)
+    local old_path = leaving and M.current.Path
+    local session = M.opts.sessions == true and (not M.current or leaving)
+        and rpc.req(rpc_names.load_session, list, ws.Id)
+    if leaving then
+        M.SaveSession()
+    end
+    if session and session ~= vim.NIL then
+        restore_session(session, old_path)
+        return
+    end
+    -- Nothing saved yet, start the workspace from its path
+    if leaving and M.opts.sessions == true then
+        clear_session(old_path)
+    end
     vim.cmd("edit " .. ws.Path)
     vim.cmd("cd " .. ws.Path)
 end
 
-M.WorkspacersList = function(opts)
-    rpc.req_res(rpc_names.list, function(rpc_obj)
-        -- Arrange into lua friendly format
-        local fmt_vals = {}
-        local ws_by_fmt = {} -- Have a keyed table to lookup in preview
-        for _, entry in pairs(rpc_obj) do
-            for fmt, ws in pairs(entry) do
-                table.insert(fmt_vals, fmt)
-                ws_by_fmt[fmt] = ws
-            end
+-- Arrange the entries from WORKSPACERS.LIST into lua friendly format
+local function set_records(opts, rpc_obj)
+    local fmt_vals = {}
+    local workspaces = {} -- Same order as fmt_vals so entries can be looked up by index
+    local highlights = {}
+    for _, entry in ipairs(rpc_obj) do
+        for fmt, ws in pairs(entry) do
+            table.insert(fmt_vals, fmt)
+            table.insert(workspaces, ws)
+            table.insert(highlights, ws.Highlights or {})
         end
-        opts.records = fmt_vals
-        opts.ws_by_fmt = ws_by_fmt
+    end
+    opts.records = fmt_vals
+    opts.workspaces = workspaces
+    opts.highlights = highlights
+end
+
+local function request_list(opts, on_list)
+    if opts.all then
+        rpc.req_res(rpc_names.list_all, on_list, opts.sort, opts.tags)
+    else
+        rpc.req_res(rpc_names.list, on_list, opts.ws_name, opts.sort, opts.tags)
+    end
+end
+
+M.WorkspacersList = function(opts)
+    local on_list = function(rpc_obj)
+        set_records(opts, rpc_obj)
         opts.callback = function(call_opts)
             call_opts.close()
-            if call_opts.selected and call_opts.selected[1] then
-                select_workspace(ws_by_fmt[call_opts.selected[1]])
+            local selected = selected_ws(call_opts)
+            if selected then
+                select_workspace(call_opts, selected)
             else
                 vim.notify("No selected Workspace", vim.log.levels.ERROR)
             end
@@ -155,7 +403,7 @@ M.WorkspacersList = function(opts)
         opts.previewer = require('telescope.previewers').new_buffer_previewer({
             title = "Preview",
             define_preview = function(self, entry, _)
-                local path = ws_by_fmt[entry.value].Path
+                local path = opts.workspaces[entry.index].Path
                 require('telescope.previewers').buffer_previewer_maker(path, self.state.bufnr, {
                     use_ft_detect = true
                 })
@@ -163,11 +411,34 @@ M.WorkspacersList = function(opts)
         })
         opts.keys = M.opts.keys
         opts.get_preview_content = function(entry)
-            return ws_by_fmt[entry.value].Path
+            return opts.workspaces[entry.index].Path
         end
 
+        M.active = opts
         tele.pick(opts)
-    end, opts.ws_name)
+    end
+    request_list(opts, on_list)
+end
+
+-- Reload the open picker when a list it shows changes on disk, e.g. after an edit from the CLI
+-- data: { event = "WORKSPACERS.CHANGED", list = "notes", kind = "created" | "modified" | "removed" }
+M.OnChanged = function(data)
+    local opts = M.active
+    if not opts or not tele.is_open(opts) then
+        M.active = nil
+        return
+    end
+    if not opts.all and opts.ws_name ~= data.list then return end
+    request_list(opts, function(rpc_obj)
+        set_records(opts, rpc_obj)
+        tele.refresh(opts)
+    end)
+end
+
+-- Lists workspaces from every list, actions are sent to the list each entry came from
+M.WorkspacersListAll = function(opts)
+    opts.all = true
+    M.WorkspacersList(opts)
 end
 
 M.WorkspacersJson = function(ws_name)
@@ -176,38 +447,50 @@ M.WorkspacersJson = function(ws_name)
     end, ws_name)
 end
 
+M.TagWorkspace = function(opts, add)
+    local selected = selected_ws(opts)
+    if not selected then
+        vim.notify("No selected Workspace", vim.log.levels.ERROR)
+        return
+    end
+    local input_opts = { prompt = "Enter Tag to Add: " }
+    if not add then
+        input_opts = { prompt = "Enter Tag to Remove: ", default = (selected.Tags or {})[1] }
+    end
+    local tag, success = try_get_input(input_opts)
+    if not success then return end
+
+    local rpc_action = add and rpc_names.add_tag or rpc_names.remove_tag
+    rpc.req_res(rpc_action, function()
+        opts.close()
+        M.WorkspacersList(opts)
+    end, list_name(opts, selected), selected.Id, tag)
+end
+
 M.PromoteWorkspace = function(opts, promote)
     if opts.text and opts.text ~= "" then
         vim.notify("Cannot reorder with search text", vim.log.levels.ERROR)
         return
     end
-
-    local rpc_action = promote and rpc_names.promote or rpc_names.demote
-    if opts.selected and opts.selected[1] then
-        rpc.req_res(rpc_action, function(new_idx)
-                opts.close()
-                opts.selected_idx = new_idx
-                M.WorkspacersList(opts)
-            end,
-            opts.ws_name, opts.selected[1])
-    else
-        vim.notify("No selected Workspace", vim.log.levels.ERROR)
+    -- The returned index is within the whole list, which only matches the rows shown when nothing is filtered
+    if opts.tags and #opts.tags > 0 then
+        vim.notify("Cannot reorder with a tag filter", vim.log.levels.ERROR)
+        return
     end
-end
 
-M.DemoteWorkspace = function(opts)
-    if opts.text and opts.text ~= "" then
-        vim.notify("Cannot Demote with search text", vim.log.levels.ERROR)
+    local rpc_action = promote and rpc_names.promote or rpc_names.demote
+    local selected = selected_ws(opts)
+    if selected and sort_mode(opts, list_name(opts, selected)) ~= "manual" then
+        vim.notify("Cannot reorder a list that isn't sorted manually", vim.log.levels.ERROR)
         return
     end
-    if opts.selected and opts.selected[1] then
-        rpc.req_res(rpc_names.demote,
-            function(new_idx)
+    if selected then
+        rpc.req_res(rpc_action, function(new_idx)
                 opts.close()
-                opts.selected_idx = new_idx
+                opts.selected_idx = new_idx + list_offset(opts, selected)
                 M.WorkspacersList(opts)
             end,
-            opts.ws_name, opts.selected[1])
+            list_name(opts, selected), selected.Id)
     else
         vim.notify("No selected Workspace", vim.log.levels.ERROR)
     end
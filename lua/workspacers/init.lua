@@ -7,24 +7,75 @@ local function setup_commands()
     vim.api.nvim_create_user_command('WorkspacersAdd',
         function(o) M.WorkspacersAdd(o.args) end, { nargs = 1 })
     vim.api.nvim_create_user_command('WorkspacersJson',
-        function(o) M.WorkspacersJson(o.args) end, { nargs = 1 })
+        function(o) M.WorkspacersJson(o.fargs[1]) end, { nargs = '?' })
+    -- :WorkspacersList [list] [tags...], the default list from the config when no list is given
     vim.api.nvim_create_user_command('WorkspacersList',
-        function(o) cmds.WorkspacersList({ ws_name = o.args }) end, { nargs = 1 })
+        function(o) M.WorkspacersList(o.fargs[1], { unpack(o.fargs, 2) }) end, { nargs = '*' })
+    -- :WorkspacersListAll [tags...]
+    vim.api.nvim_create_user_command('WorkspacersListAll',
+        function(o) M.WorkspacersListAll(o.fargs) end, { nargs = '*' })
 end
 
-M.WorkspacersList = function(ws_name)
-    M.opts.ws_name = ws_name
+-- List used when none is given, `default_list` in the config file
+local function default_list()
+    local config = cmds.config or cmds.LoadConfig()
+    return config and config.DefaultList
+end
+
+---@param ws_name string|nil: List to show, the default list when nil
+---@param tags string[]|nil: Only list workspaces with all of these tags
+M.WorkspacersList = function(ws_name, tags)
+    M.opts.ws_name = ws_name or default_list()
+    M.opts.tags = tags
+    M.opts.all = false
     cmds.WorkspacersList(M.opts)
 end
 
+---@param tags string[]|nil: Only list workspaces with all of these tags
+M.WorkspacersListAll = function(tags)
+    M.opts.ws_name = nil
+    M.opts.tags = tags
+    cmds.WorkspacersListAll(M.opts)
+end
+
 M.WorkspacersJson = function(ws_name)
-    M.opts.ws_name = ws_name
+    M.opts.ws_name = ws_name or default_list()
     cmds.WorkspacersJson(M.opts)
 end
 
----@param opts.json_dir string: Json dir to be used(If using custom location)
+-- Name of the workspace of the cwd, "" when outside every workspace
+-- For lualine: sections = { lualine_c = { require('workspacers').statusline } }
+M.statusline = function()
+    return (cmds.current and cmds.current.Name) or ""
+end
+
+-- Workspace containing `path` (default: the cwd) as { Id, Name, Path, Tags, List }, nil when there is none
+---@param path string|nil
+M.current = function(path)
+    if path == nil then
+        return cmds.current
+    end
+    return cmds.Current(path)
+end
+
+---@param opts.json_dir string: Json dir to be used(If using custom location), overrides data_dir in the config
+---@param opts.config_file string: Config file, defaults to workspacers/workspacers.toml in the config directory
+---@param opts.log_level string: `off` | `error` | `warn` | `info` | `debug` | `trace`, overrides log_level in the config
 ---@param opts.binary string: Direct path to workspacers-nvim binary(If not in path)
 ---@param opts.theme string: Telescope theme: `ivy` |` dropdown` | `cursor`
+---@param opts.sessions bool: Save the buffers, windows and cwd of a workspace when switching away from it
+---                           or quitting, and restore them when it is next selected (default: false)
+---                           Switching closes the windows and the unmodified buffers under the old workspace's path
+---@param opts.sort string|table: `manual` | `frecency`, or a table of modes by list name e.g. { notes = 'frecency' }
+---                                Lists without a mode use `list_sort` or `sort` from the config
+--- When a list file changes on disk a `User WorkspacersChanged` autocmd fires with
+--- data = { event = "WORKSPACERS.CHANGED", list = <list name>, kind = "created" | "modified" | "removed" },
+--- an open picker showing that list is refreshed
+--- When the cwd moves into or out of a workspace `User WorkspacersLeave` and `User WorkspacersEnter` fire,
+--- with data = { Id, Name, Path, Tags, List } of the workspace, e.g. for per-workspace settings:
+---     vim.api.nvim_create_autocmd('User', { pattern = 'WorkspacersEnter', callback = function(ev)
+---         if ev.data.Name == 'notes' then vim.opt_local.spell = true end
+---     end })
 M.setup = function(opts)
     M.opts = opts or {}
     M.opts.theme = opts.theme or 'ivy'
@@ -3,7 +3,13 @@ local M = {}
 local start_job = function(opts)
     local args = { opts.binary }
     if opts.json_dir then
-        table.insert(args, '--json-dir=' .. M.opts.json_dir)
+        table.insert(args, '--json-dir=' .. opts.json_dir)
+    end
+    if opts.config_file then
+        table.insert(args, '--config-file=' .. opts.config_file)
+    end
+    if opts.log_level then
+        table.insert(args, '--log-level=' .. opts.log_level)
     end
     return vim.fn.jobstart(
         args,
@@ -20,16 +26,69 @@ local start_job = function(opts)
     )
 end
 
+-- Errors are sent as JSON: { "code": "missing_argument", "message": "Missing arg[1] id", "field": "arg[1] id" }
+-- vim.rpcrequest raises them as "Error invoking '<method>' on channel <id>:\n<error>"
+local function decode_error(err)
+    local json = tostring(err):match("\n(%b{})%s*$")
+    local ok, decoded = pcall(vim.json.decode, json or "")
+    if ok and type(decoded) == "table" and decoded.message then
+        return decoded
+    end
+    return { code = "unknown", message = tostring(err) }
+end
+
+-- Returns the result, or nil after showing the reason when the request failed
 M.req = function(method, ...)
-    -- vim.print('sending request: ', method)
-    return vim.rpcrequest(M.job_id, method, ...)
+    local ok, result = pcall(vim.rpcrequest, M.job_id, method, ...)
+    if ok then
+        return result
+    end
+    local err = decode_error(result)
+    vim.notify("Workspacers: " .. err.message, vim.log.levels.ERROR)
+    return nil, err
+end
+
+-- Protocol version of workspacers-nvim this plugin is written for, see PROTOCOL_VERSION in rpc_commands.rs
+M.PROTOCOL = 2
+
+-- Checks the binary handles every method in `methods`, telling the user to rebuild it when it doesn't
+-- Binaries from before the handshake reply false to unknown requests
+M.check_version = function(methods)
+    -- Protocol sent as a string, older binaries read arg[0] of every request as a string
+    local ok, hello = pcall(vim.rpcrequest, M.job_id, 'WORKSPACERS.HELLO', tostring(M.PROTOCOL))
+    local problem
+    if not ok or type(hello) ~= 'table' then
+        problem = "does not support WORKSPACERS.HELLO"
+    elseif hello.Protocol ~= M.PROTOCOL then
+        problem = string.format("uses protocol %s, the plugin needs protocol %d", tostring(hello.Protocol), M.PROTOCOL)
+    else
+        local missing = {}
+        for _, method in ipairs(methods) do
+            if not vim.tbl_contains(hello.Methods or {}, method) then
+                table.insert(missing, method)
+            end
+        end
+        if #missing > 0 then
+            problem = "does not support " .. table.concat(missing, ", ")
+        end
+    end
+
+    M.compatible = problem == nil
+    if problem then
+        local version = type(hello) == 'table' and hello.Version or "unknown version"
+        vim.notify(string.format(
+            "Workspacers: %s (%s) %s. Please rebuild workspacers-nvim: cargo build --release",
+            M.opts.binary, version, problem
+        ), vim.log.levels.ERROR)
+    end
+    return M.compatible
 end
 
 M.req_res = function(name, callback, ...)
-    local result = M.req(name, ...)
+    local result, err = M.req(name, ...)
     if result then
         callback(result)
-    else
+    elseif not err then
         print("No result available")
     end
     return result
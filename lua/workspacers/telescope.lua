@@ -7,6 +7,39 @@ local actions = require("telescope.actions")
 local action_state = require "telescope.actions.state"
 local themes = require("telescope.themes")
 
+-- Groups sent in the Highlights of each entry, linked by default so colorschemes and users can override them
+local highlight_links = {
+    WorkspacersList = "Keyword",
+    WorkspacersIcon = "Special",
+    WorkspacersName = "Title",
+    WorkspacersPath = "Directory",
+    WorkspacersTags = "Type",
+    WorkspacersLastOpened = "Comment",
+    WorkspacersGitBranch = "String",
+    WorkspacersIndex = "LineNr",
+}
+
+M.setup_highlights = function()
+    for group, link in pairs(highlight_links) do
+        vim.api.nvim_set_hl(0, group, { link = link, default = true })
+    end
+end
+
+-- Telescope entry for a record, coloured by the column highlights from WORKSPACERS.LIST
+local function make_entry(result)
+    local highlights = {}
+    for _, hl in ipairs(result.highlights) do
+        table.insert(highlights, { { hl.Start, hl.End }, hl.Hl })
+    end
+    return {
+        value = result.record,
+        ordinal = result.record,
+        display = function()
+            return result.record, highlights
+        end,
+    }
+end
+
 local close = function(opts)
     actions.close(opts.bufnr)
 end
@@ -16,6 +49,15 @@ local select_callback = function(opts)
     opts.callback(opts)
 end
 
+-- Widest record in screen cells, capped to the editor so long rows don't overflow it
+local function records_width(records)
+    local width = 0
+    for _, record in ipairs(records) do
+        width = math.max(width, vim.fn.strdisplaywidth(record))
+    end
+    return math.min(width + 5, vim.o.columns)
+end
+
 local function get_theme(opts)
     if type(opts.theme) == "function" then
         return opts.theme()
@@ -27,7 +69,7 @@ local function get_theme(opts)
     if opts.theme == 'ivy' then
         return themes.get_ivy({
             layout_config = {
-                width = string.len(opts.records[1] or "") + 5,
+                width = records_width(opts.records),
                 height = #opts.records + 4,
             }
         })
@@ -48,15 +90,25 @@ local function get_theme(opts)
     end
 end
 
+local function new_finder(opts)
+    local results = {}
+    for i, record in ipairs(opts.records) do
+        table.insert(results, { record = record, highlights = (opts.highlights or {})[i] or {} })
+    end
+    return finders.new_table {
+        results = results,
+        entry_maker = make_entry,
+    }
+end
+
 --@params opts table Options
 --@params opts.records string[] Records to be displayed
+--@params opts.highlights table[] Highlights of each record, as sent by WORKSPACERS.LIST
 --@params opts.close bool Should close on select (default: true)
 --@params opts.callback function Callback on close
 M.pick = function(opts)
     opts = opts or {}
-    opts.finder = finders.new_table {
-        results = opts.records
-    }
+    opts.finder = new_finder(opts)
     opts.sorter = conf.generic_sorter({})
     -- opts.previewer = opts.previewer or previewers.new_buffer_previewer({
     --     define_preview = function(self, entry, status)
@@ -91,4 +143,18 @@ M.pick = function(opts)
     picker:find()
 end
 
+-- Whether the picker opened by M.pick is still showing
+M.is_open = function(opts)
+    return opts.bufnr ~= nil and vim.api.nvim_buf_is_valid(opts.bufnr)
+end
+
+-- Replace the entries of an open picker with opts.records, keeping the prompt text
+M.refresh = function(opts)
+    if not M.is_open(opts) then return end
+    local picker = action_state.get_current_picker(opts.bufnr)
+    if picker then
+        picker:refresh(new_finder(opts), { reset_prompt = false })
+    end
+end
+
 return M
@@ -1,3 +1,5 @@
+# Hand-written integration, `workspacers-cli init powershell` generates the equivalent for every shell
+#
 # Override the path to the exe(if you don't want to add it to your path)
 #
 $WsExe = "workspacers-cli"
@@ -1,17 +1,34 @@
 use common::formatter;
-use common::json::{self, Workspace};
+use common::icons::IconKind;
+use common::json::Workspace;
+use common::store::{self, WorkspaceStore};
+use rustyline::{Behavior, Config, DefaultEditor};
 use std::env::current_dir;
-use std::path::PathBuf;
-
-pub fn add(workspaces: &Vec<Workspace>, json_path: &PathBuf) -> Result<(), String> {
-    let new_ws = read_new_workspace(&workspaces)?;
-    println!("Adding Workspace: {new_ws}");
-    let mut new_workspaces = workspaces.clone();
-    new_workspaces.insert(workspaces.len(), new_ws);
-    json::write_workspaces(json_path, &new_workspaces).map_err(|e| format!("Failed to write workspaces: {e}"))
+use std::path::{Component, Path, PathBuf};
+
+pub fn add(store: &mut WorkspaceStore) -> Result<(), String> {
+    let new_ws = read_new_workspace(store.workspaces())?;
+    eprintln!("Adding Workspace: {new_ws}");
+    // Validated again against the latest list in case it was changed while reading input
+    store
+        .add(new_ws)
+        .map_err(|e| format!("Failed to write workspaces: {e}"))
+}
+
+pub fn read_new_workspace(workspaces: &[Workspace]) -> Result<Workspace, String> {
+    let cwd = get_cwd()?;
+
+    let name = read_line("Name".to_string(), "")?;
+    validate_new_workspace(workspaces, &name, "")?;
+
+    let path = absolute_path(formatter::unfmt_path(read_line("Path".to_string(), &cwd)?))?;
+    validate_new_workspace(workspaces, &name, &path)?;
+
+    Ok(Workspace::new(name, path))
 }
 
-fn read_new_workspace(workspaces: &Vec<Workspace>) -> Result<Workspace, String> {
+// Current directory with a trailing separator, the default path for new workspaces
+pub fn get_cwd() -> Result<String, String> {
     let mut cwd = current_dir()
         .map_err(|_| "Failed to get current directory")?
         .to_string_lossy()
@@ -19,26 +36,39 @@ fn read_new_workspace(workspaces: &Vec<Workspace>) -> Result<Workspace, String>
     if !cwd.ends_with(std::path::MAIN_SEPARATOR) {
         cwd.push(std::path::MAIN_SEPARATOR);
     }
+    Ok(cwd)
+}
 
-    let name = read_line("Name".to_string(), "")?;
-    if workspaces.iter().any(|ws| ws.name == name) {
-        return Err(format!("Workspace Name already exists: '{name}'"));
-    }
-
-    let path = read_line("Path".to_string(), &cwd)?;
-    if workspaces.iter().any(|ws| ws.path == path) {
-        return Err(format!("Workspace Path already exists: '{path}'"));
+// Relative paths are resolved against the current directory, urls and remote paths are kept as given
+pub fn absolute_path(path: String) -> Result<String, String> {
+    if matches!(IconKind::of(&path), IconKind::Url | IconKind::Remote) || Path::new(&path).is_absolute() {
+        return Ok(path);
     }
+    let absolute = std::path::absolute(&path).map_err(|err| format!("Could not resolve path '{path}': {err}"))?;
+    // Lexically, so paths that don't exist yet can be added
+    let resolved = absolute.components().fold(PathBuf::new(), |mut resolved, component| {
+        match component {
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            Component::CurDir => {}
+            component => resolved.push(component),
+        }
+        resolved
+    });
+    Ok(resolved.to_string_lossy().to_string())
+}
 
-    Ok(Workspace {
-        name,
-        path: formatter::unfmt_path(path),
-    })
+fn validate_new_workspace(workspaces: &[Workspace], name: &str, path: &str) -> Result<(), String> {
+    store::validate_workspace(workspaces, &Workspace::new(name.to_string(), path.to_string()))
+        .map_err(|err| err.to_string())
 }
 
-fn read_line(property_name: String, initial: &str) -> Result<String, String> {
-    let user_value = rustyline::DefaultEditor::new()
-        .unwrap()
+// Prompts on the terminal rather than stdout, which the shell integration captures as the path to cd into
+pub fn read_line(property_name: String, initial: &str) -> Result<String, String> {
+    let config = Config::builder().behavior(Behavior::PreferTerm).build();
+    let user_value = DefaultEditor::with_config(config)
+        .map_err(|err| format!("Could not read input: {err}"))?
         .readline_with_initial(&format!("Enter {property_name}: "), (initial, ""))
         .map_err(|_| "Operation Cancelled".to_string())?;
 
@@ -47,3 +77,18 @@ fn read_line(property_name: String, initial: &str) -> Result<String, String> {
         false => Ok(user_value),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relative_paths_are_made_absolute() {
+        let cwd = current_dir().unwrap();
+        let parent = cwd.parent().unwrap().to_string_lossy().to_string();
+        assert_eq!(absolute_path(".".to_string()).unwrap(), cwd.to_string_lossy());
+        assert_eq!(absolute_path("./a/../..".to_string()).unwrap(), parent);
+        assert_eq!(absolute_path("/src/app".to_string()).unwrap(), "/src/app");
+        assert_eq!(absolute_path("git@host:app".to_string()).unwrap(), "git@host:app");
+    }
+}
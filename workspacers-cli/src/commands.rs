@@ -0,0 +1,172 @@
+use clap::Subcommand;
+use common::formatter;
+use common::json::{self, Workspace};
+use common::setup::path;
+use common::sort::{self, SortMode};
+use common::store::WorkspaceStore;
+use common::template::Template;
+use std::path::Path;
+
+use crate::add_workspace;
+use crate::migrate;
+use crate::output::{self, OutputFormat};
+use crate::shell_init::{self, Shell};
+
+// Non-interactive commands, these never prompt so can be used from scripts and CI
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Print the workspaces in the list
+    List,
+
+    /// Add a workspace
+    Add {
+        #[arg(long)]
+        name: String,
+
+        /// Defaults to the current directory
+        #[arg(long)]
+        path: Option<String>,
+    },
+
+    /// Remove a workspace
+    Remove { name: String },
+
+    /// Rename a workspace
+    Rename { name: String, new_name: String },
+
+    /// Move a workspace to a position in the list
+    Move {
+        name: String,
+
+        /// Index to move to, starting at 0
+        #[arg(long)]
+        to: usize,
+    },
+
+    /// Print a workspace as JSON
+    Get { name: String },
+
+    /// Print the path of a workspace
+    Path { name: String },
+
+    /// Upgrade the list to the current schema version, shows the changes without writing unless --apply is given
+    Migrate {
+        /// Write the upgraded list, the previous list is kept as a backup
+        #[arg(long)]
+        apply: bool,
+    },
+
+    /// Replace the list with one of its backups, the current list is kept as the newest backup
+    Restore {
+        /// Backup to restore, 1 is the newest
+        #[arg(long, default_value_t = 1)]
+        generation: usize,
+    },
+
+    /// Print shell integration code, e.g. add `eval "$(workspacers-cli init bash)"` to ~/.bashrc
+    Init {
+        shell: Shell,
+
+        /// Name of the shell function
+        #[arg(long, default_value = "ws")]
+        cmd: String,
+
+        /// Key to open the picker from the prompt, e.g. ctrl-g
+        #[arg(long)]
+        bind: Option<String>,
+    },
+}
+
+// What the commands need from the arguments and config
+pub struct RunOptions<'a> {
+    pub json_file: &'a Path,
+    pub tags: &'a [String],
+    pub sort: SortMode,
+    pub template: &'a Template,
+    pub output: Option<OutputFormat>,
+    // Names of the subcommands and value flags, used by the shell integration
+    pub cli_names: shell_init::CliNames<'a>,
+}
+
+pub fn run(command: Command, opts: &RunOptions) -> Result<(), String> {
+    // Opened by the commands that use the list, init doesn't need one and migrate reads it itself
+    let open = || WorkspaceStore::open(opts.json_file).map_err(|err| err.to_string());
+    match command {
+        Command::List => {
+            let store = open()?;
+            let shown = sort::sorted(&json::filter_by_tags(store.workspaces(), opts.tags), opts.sort);
+            if let Some(format) = opts.output {
+                return output::print_workspaces(store.workspaces(), &shown, format);
+            }
+            for (ws_str, _) in formatter::fmt(&shown, opts.template) {
+                println!("{ws_str}");
+            }
+            Ok(())
+        }
+        Command::Add { name, path } => {
+            let path = add_workspace::absolute_path(formatter::unfmt_path(match path {
+                Some(path) => path,
+                None => add_workspace::get_cwd()?,
+            }))?;
+            open()?.add(Workspace::new(name, path)).map_err(|err| err.to_string())
+        }
+        Command::Remove { name } => {
+            let mut store = open()?;
+            let id = find(&store, &name)?.id.to_string();
+            store.remove(&id).map(|_| ()).map_err(|err| err.to_string())
+        }
+        Command::Rename { name, new_name } => {
+            let mut store = open()?;
+            let id = find(&store, &name)?.id.to_string();
+            store
+                .update(&id, |ws| ws.name = new_name)
+                .map(|_| ())
+                .map_err(|err| err.to_string())
+        }
+        Command::Move { name, to } => {
+            let mut store = open()?;
+            let id = find(&store, &name)?.id.to_string();
+            store.move_to(&id, to).map_err(|err| err.to_string())
+        }
+        Command::Get { name } => {
+            let store = open()?;
+            let ws = find(&store, &name)?;
+            if let Some(format) = opts.output {
+                return output::print_workspace(store.workspaces(), ws, format);
+            }
+            let ws_json = serde_json::to_string_pretty(ws).map_err(|err| format!("Could not serialize: {err}"))?;
+            println!("{ws_json}");
+            Ok(())
+        }
+        Command::Path { name } => {
+            let store = open()?;
+            let ws = find(&store, &name)?;
+            if let Some(format) = opts.output {
+                return output::print_workspace(store.workspaces(), ws, format);
+            }
+            println!("{}", ws.path);
+            Ok(())
+        }
+        Command::Init { shell, cmd, bind } => {
+            let exe = path::get_binary_name().map_err(|err| err.to_string())?;
+            print!(
+                "{}",
+                shell_init::init(shell, &exe, &cmd, bind.as_deref(), &opts.cli_names)?
+            );
+            Ok(())
+        }
+        Command::Restore { generation } => {
+            json::restore_backup(opts.json_file, generation).map_err(|err| err.to_string())?;
+            eprintln!("Restored {} from backup {generation}", opts.json_file.to_string_lossy());
+            Ok(())
+        }
+        // Reads the list itself, only writing it with --apply
+        Command::Migrate { apply } => migrate::run(opts.json_file, apply),
+    }
+}
+
+fn find<'a>(store: &'a WorkspaceStore, name: &str) -> Result<&'a Workspace, String> {
+    store
+        .find_by_name(name)
+        .ok_or_else(|| format!("Workspace not found: '{name}'"))
+}
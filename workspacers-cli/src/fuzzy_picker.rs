@@ -0,0 +1,282 @@
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    execute, queue,
+    style::{Attribute, Color, Print, ResetColor, SetAttribute, SetForegroundColor},
+    terminal::{self, ClearType},
+};
+use fuzzy_matcher::FuzzyMatcher;
+use fuzzy_matcher::skim::SkimMatcherV2;
+use std::cmp::Reverse;
+use std::io::{Stderr, Write, stderr};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::picker::{ACTION_KEYS, Picked, PickerAction};
+
+const PROMPT: &str = "> ";
+
+// A row that matched the query, with the char indices that matched for highlighting
+struct Match {
+    row_idx: usize,
+    score: i64,
+    indices: Vec<usize>,
+}
+
+struct PickerState {
+    query: String,
+    matches: Vec<Match>,
+    selected: usize,
+    offset: usize,
+    header: Vec<String>,
+}
+
+// Restores the terminal when dropped, including on errors and panics
+struct TerminalGuard {
+    out: Stderr,
+}
+
+impl TerminalGuard {
+    fn enter() -> Result<TerminalGuard, String> {
+        let mut out = stderr();
+        terminal::enable_raw_mode().map_err(|err| format!("picker - could not enable raw mode: {err}"))?;
+        execute!(out, terminal::EnterAlternateScreen)
+            .map_err(|err| format!("picker - could not setup terminal: {err}"))?;
+        Ok(TerminalGuard { out })
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = execute!(self.out, cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+// Built in fuzzy picker, drawn on stderr so stdout only has the selection
+// Starts on the `selected` row, the match count is shown after the first header line
+pub fn pick(rows: &[String], selected: usize, header: &str) -> Result<Picked, String> {
+    let matcher = SkimMatcherV2::default();
+    let mut state = PickerState {
+        query: String::new(),
+        matches: Vec::new(),
+        selected: 0,
+        offset: 0,
+        header: header.lines().map(String::from).collect(),
+    };
+    update_matches(&matcher, rows, &mut state);
+    state.selected = selected.min(state.matches.len().saturating_sub(1));
+
+    let mut guard = TerminalGuard::enter()?;
+    loop {
+        draw(&mut guard.out, rows, &mut state).map_err(|err| format!("picker - could not draw: {err}"))?;
+
+        let key = match event::read().map_err(|err| format!("picker - could not read input: {err}"))? {
+            Event::Key(key) if key.kind != KeyEventKind::Release => key,
+            _ => continue,
+        };
+        let row_idx = state.matches.get(state.selected).map(|m| m.row_idx);
+        match handle_key(key, &mut state) {
+            KeyAction::Select => match row_idx {
+                Some(row_idx) => return Ok(Picked::Selected(row_idx)),
+                None => continue,
+            },
+            KeyAction::Cancel => return Ok(Picked::Cancelled),
+            KeyAction::Action(action) => return Ok(Picked::Action(action, row_idx)),
+            KeyAction::QueryChanged => update_matches(&matcher, rows, &mut state),
+            KeyAction::None => {}
+        }
+    }
+}
+
+enum KeyAction {
+    Select,
+    Cancel,
+    Action(PickerAction),
+    QueryChanged,
+    None,
+}
+
+fn handle_key(key: KeyEvent, state: &mut PickerState) -> KeyAction {
+    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+    match key.code {
+        KeyCode::Enter => KeyAction::Select,
+        KeyCode::Esc => KeyAction::Cancel,
+        KeyCode::Char('c') if ctrl => KeyAction::Cancel,
+        KeyCode::Up => move_selection(state, -1),
+        KeyCode::Char('p') if ctrl => move_selection(state, -1),
+        KeyCode::Down => move_selection(state, 1),
+        KeyCode::Char('n') if ctrl => move_selection(state, 1),
+        KeyCode::Char(c) if ctrl => match ACTION_KEYS.iter().find(|(_, name)| *name == format!("ctrl-{c}")) {
+            Some((action, _)) => KeyAction::Action(*action),
+            None => KeyAction::None,
+        },
+        KeyCode::Backspace => match state.query.pop() {
+            Some(_) => KeyAction::QueryChanged,
+            None => KeyAction::None,
+        },
+        KeyCode::Char(c) if !ctrl => {
+            state.query.push(c);
+            KeyAction::QueryChanged
+        }
+        _ => KeyAction::None,
+    }
+}
+
+fn move_selection(state: &mut PickerState, step: isize) -> KeyAction {
+    if !state.matches.is_empty() {
+        let last = state.matches.len() - 1;
+        state.selected = state.selected.saturating_add_signed(step).min(last);
+    }
+    KeyAction::None
+}
+
+// Best score first, ties keep the order of the rows
+fn update_matches(matcher: &SkimMatcherV2, rows: &[String], state: &mut PickerState) {
+    state.matches = rows
+        .iter()
+        .enumerate()
+        .filter_map(|(row_idx, row)| match state.query.is_empty() {
+            true => Some(Match {
+                row_idx,
+                score: 0,
+                indices: Vec::new(),
+            }),
+            false => matcher.fuzzy_indices(row, &state.query).map(|(score, indices)| Match {
+                row_idx,
+                score,
+                indices,
+            }),
+        })
+        .collect();
+    state.matches.sort_by_key(|m| Reverse(m.score));
+    state.selected = 0;
+    state.offset = 0;
+}
+
+// The chars of `row` fitting in `space` columns, with whether they matched the query
+// Stops at the edge of the terminal, wide chars such as emoji take two columns
+fn visible_chars(row: &str, indices: &[usize], mut space: usize) -> Vec<(char, bool)> {
+    let mut chars = Vec::new();
+    for (char_idx, c) in row.chars().enumerate() {
+        let Some(rest) = space.checked_sub(c.width().unwrap_or(0)) else {
+            break;
+        };
+        space = rest;
+        chars.push((c, indices.contains(&char_idx)));
+    }
+    chars
+}
+
+fn draw(out: &mut Stderr, rows: &[String], state: &mut PickerState) -> std::io::Result<()> {
+    let (width, height) = terminal::size()?;
+    // Lines used by the header and prompt
+    let reserved = state.header.len().max(1) as u16 + 1;
+    let visible = height.saturating_sub(reserved).max(1) as usize;
+
+    // Scroll to keep the selection on screen
+    if state.selected < state.offset {
+        state.offset = state.selected;
+    } else if state.selected >= state.offset + visible {
+        state.offset = state.selected + 1 - visible;
+    }
+
+    queue!(
+        out,
+        terminal::Clear(ClearType::All),
+        SetForegroundColor(Color::DarkGrey)
+    )?;
+    let count = format!("{}/{}", state.matches.len(), rows.len());
+    for (line, text) in state.header.iter().enumerate() {
+        queue!(out, cursor::MoveTo(0, line as u16))?;
+        match line {
+            0 => queue!(out, Print(format!("{text} {count}")))?,
+            // Later lines are messages from actions
+            _ => queue!(out, SetForegroundColor(Color::Yellow), Print(text))?,
+        }
+    }
+    queue!(
+        out,
+        ResetColor,
+        cursor::MoveTo(0, reserved - 1),
+        Print(PROMPT),
+        Print(&state.query)
+    )?;
+
+    for (line, m) in state.matches.iter().skip(state.offset).take(visible).enumerate() {
+        let is_selected = state.offset + line == state.selected;
+        queue!(out, cursor::MoveTo(0, line as u16 + reserved))?;
+        if is_selected {
+            queue!(out, SetAttribute(Attribute::Reverse), Print(PROMPT))?;
+        } else {
+            queue!(out, Print(" ".repeat(PROMPT.len())))?;
+        }
+
+        let space = (width as usize).saturating_sub(PROMPT.len());
+        for (c, matched) in visible_chars(&rows[m.row_idx], &m.indices, space) {
+            if matched {
+                queue!(
+                    out,
+                    SetForegroundColor(Color::Green),
+                    SetAttribute(Attribute::Bold),
+                    Print(c),
+                    SetAttribute(Attribute::NormalIntensity),
+                    ResetColor,
+                )?;
+                if is_selected {
+                    queue!(out, SetAttribute(Attribute::Reverse))?;
+                }
+            } else {
+                queue!(out, Print(c))?;
+            }
+        }
+        queue!(out, SetAttribute(Attribute::Reset))?;
+    }
+
+    // Show the cursor at the end of the query
+    let query_end = (PROMPT.len() + state.query.width()) as u16;
+    queue!(out, cursor::MoveTo(query_end, reserved - 1), cursor::Show)?;
+    out.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matched_rows(rows: &[String], query: &str) -> Vec<usize> {
+        let mut state = PickerState {
+            query: query.to_string(),
+            matches: Vec::new(),
+            selected: 3,
+            offset: 2,
+            header: Vec::new(),
+        };
+        update_matches(&SkimMatcherV2::default(), rows, &mut state);
+        assert_eq!((state.selected, state.offset), (0, 0));
+        state.matches.iter().map(|m| m.row_idx).collect()
+    }
+
+    #[test]
+    fn matches_are_ordered_by_score() {
+        let rows: Vec<String> = ["notes - ~/notes", "web - ~/src/web", "api - ~/src/api", "src - ~/src"]
+            .iter()
+            .map(|row| row.to_string())
+            .collect();
+        assert_eq!(matched_rows(&rows, ""), vec![0, 1, 2, 3]);
+        assert_eq!(matched_rows(&rows, "src"), vec![3, 1, 2]);
+        assert!(matched_rows(&rows, "zzz").is_empty());
+    }
+
+    #[test]
+    fn matched_chars_are_highlighted() {
+        let matcher = SkimMatcherV2::default();
+        let row = "📁 web";
+        let (_, indices) = matcher.fuzzy_indices(row, "wb").unwrap();
+        assert_eq!(
+            visible_chars(row, &indices, 80),
+            vec![('📁', false), (' ', false), ('w', true), ('e', false), ('b', true)]
+        );
+        // The emoji takes two columns, leaving no room for the rest
+        assert_eq!(visible_chars(row, &indices, 3), vec![('📁', false), (' ', false)]);
+        assert!(visible_chars(row, &indices, 1).is_empty());
+    }
+}
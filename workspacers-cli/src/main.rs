@@ -1,16 +1,40 @@
-use clap::{Parser, command};
-use common::{json, setup::logging};
+use clap::{CommandFactory, Parser, ValueEnum};
+use common::sort::SortMode;
+use common::store::WorkspaceStore;
+use common::template::Template;
+use common::{
+    json,
+    setup::{
+        config::{AppConfig, get_config},
+        logging,
+    },
+};
+use log::{error, info};
 
 mod add_workspace;
+mod commands;
+mod fuzzy_picker;
+mod migrate;
+mod output;
 mod picker;
+mod picker_actions;
+mod shell_init;
 
 #[derive(Parser, Debug)]
 #[command(long_about = None)]
 struct CliArgs {
-    /// JSON File to be used to store workspaces
-    #[arg(long = "json-file", hide = false)]
+    /// JSON File to be used to store workspaces, overrides data_dir in the config
+    #[arg(long = "json-file", hide = false, global = true)]
     json_dir: Option<std::path::PathBuf>,
 
+    /// Config file, defaults to workspacers/workspacers.toml in the config directory
+    #[arg(long = "config-file", global = true)]
+    config_file: Option<std::path::PathBuf>,
+
+    /// off | error | warn | info | debug | trace, overrides log_level in the config
+    #[arg(long, global = true)]
+    log_level: Option<String>,
+
     /// Print the JSON file used
     #[arg(short = 'j', long, default_value_t = false)]
     print_json: bool,
@@ -19,37 +43,176 @@ struct CliArgs {
     #[arg(short = 'a', long, default_value_t = false)]
     add: bool,
 
-    /// Name of list/json file to target
-    #[arg(short = 'n', long, default_value = "workspacers")]
-    name: String,
+    /// Name of list/json file to target, defaults to default_list in the config
+    #[arg(short = 'n', long)]
+    name: Option<String>,
+
+    /// Only show workspaces with this tag, can be repeated to require several tags
+    #[arg(short = 't', long = "tag", global = true)]
+    tags: Vec<String>,
+
+    /// Order of the picker: manual | frecency, overrides sort in the config
+    #[arg(short = 's', long, global = true)]
+    sort: Option<SortMode>,
+
+    /// Print workspaces as structured data instead of the formatted rows
+    #[arg(short = 'o', long, value_enum, global = true)]
+    output: Option<output::OutputFormat>,
+
+    /// Picker used to select a workspace, overrides picker in the config
+    #[arg(short = 'p', long, value_enum)]
+    picker: Option<picker::PickerBackend>,
+
+    /// Runs without the picker when given
+    #[command(subcommand)]
+    command: Option<commands::Command>,
 }
 
 // Use Result<_, String> throughout in order to capture errors to display to user
 fn main() -> Result<(), String> {
     let args = CliArgs::parse();
-    logging::setup_logger().map_err(|err| format!("Could not setup logger: {err}"))?;
-    let json_dir = json::get_json_dir(args.json_dir).unwrap();
-    let json_file = json_dir.join(format!("{}.json", args.name));
+    let (config_file, mut config) =
+        get_config(args.config_file.clone(), json::APP_NAME).map_err(|err| err.to_string())?;
+    apply_args(&mut config, &args);
+    logging::setup_logger(config.log_level_filter().map_err(|err| err.to_string())?)
+        .map_err(|err| format!("Could not setup logger: {err}"))?;
+    info!("Using config: {config_file}");
+
+    let json_dir = json::get_json_dir(config.data_dir.clone()).map_err(|err| err.to_string())?;
+    let list_name = args.name.as_deref().unwrap_or(&config.default_list);
+    let json_file = json::get_list_file(&json_dir, list_name, config.storage);
 
     if args.print_json {
-        println!("{}", json_file.to_string_lossy().to_string());
+        println!("{}", json_file.to_string_lossy());
         return Ok(());
     }
-    let workspaces = json::read_workspaces(&json_file);
+    let template = Template::from_config(&config)?;
+
+    if let Some(command) = args.command {
+        let cli = CliArgs::command();
+        let subcommands: Vec<String> = cli
+            .get_subcommands()
+            .map(|subcommand| subcommand.get_name().to_string())
+            .chain(["help".to_string()])
+            .collect();
+        let value_flags: Vec<String> = cli
+            .get_arguments()
+            .filter(|arg| arg.get_action().takes_values())
+            .flat_map(|arg| {
+                let short = arg.get_short().map(|short| format!("-{short}"));
+                let long = arg.get_long().map(|long| format!("--{long}"));
+                short.into_iter().chain(long)
+            })
+            .collect();
+        let opts = commands::RunOptions {
+            json_file: &json_file,
+            tags: &args.tags,
+            sort: config.sort_for(list_name),
+            template: &template,
+            output: args.output,
+            cli_names: shell_init::CliNames {
+                subcommands: &subcommands,
+                value_flags: &value_flags,
+            },
+        };
+        return commands::run(command, &opts);
+    }
+
+    let mut store = WorkspaceStore::open(&json_file).map_err(|err| err.to_string())?;
 
     if args.add {
-        add_workspace::add(&workspaces, &json_file)?;
+        add_workspace::add(&mut store)?;
         return Ok(());
     }
 
-    if workspaces.is_empty() {
+    if store.workspaces().is_empty() {
         return Err("No workspaces found. Add one with the -a option.".to_string());
     };
 
-    match picker::pick_workspace(workspaces)? {
+    if json::filter_by_tags(store.workspaces(), &args.tags).is_empty() {
+        return Err(format!("No workspaces found with tags: {}", args.tags.join(", ")));
+    }
+
+    let opts = picker::PickerOptions {
+        backend: picker::PickerBackend::from_str(&config.picker, true)
+            .map_err(|_| format!("Invalid picker in config: '{}' (expected native or fzf)", config.picker))?,
+        tags: &args.tags,
+        sort: config.sort_for(list_name),
+        fzf_args: &config.fzf_args,
+        template: &template,
+    };
+    match picker::pick_workspace(&mut store, &opts)? {
         None => Ok(()), // Don't print when no workspace selected
         Some(ws) => {
-            return Ok(println!("{}", &ws.path));
+            // Failing to record the open shouldn't stop the workspace being opened
+            if let Err(err) = store.record_opened(&ws.id) {
+                error!("Could not record open for '{}': {err}", ws.name);
+            }
+            match args.output {
+                Some(format) => output::print_workspace(store.workspaces(), &ws, format),
+                None => {
+                    println!("{}", &ws.path);
+                    Ok(())
+                }
+            }
         }
     }
 }
+
+// Flags override the config file and environment
+fn apply_args(config: &mut AppConfig, args: &CliArgs) {
+    if let Some(json_dir) = &args.json_dir {
+        config.data_dir = Some(json_dir.clone());
+    }
+    // A sort given for the run applies to every list
+    if let Some(sort) = args.sort {
+        config.sort = sort;
+        config.list_sort.clear();
+    }
+    if let Some(picker) = args.picker
+        && let Some(value) = picker.to_possible_value()
+    {
+        config.picker = value.get_name().to_string();
+    }
+    if let Some(log_level) = &args.log_level {
+        config.log_level = log_level.to_string();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn flags_override_the_config() {
+        let mut config = AppConfig {
+            sort: SortMode::Frecency,
+            list_sort: HashMap::from([("notes".to_string(), SortMode::Frecency)]),
+            log_level: "info".to_string(),
+            ..AppConfig::default()
+        };
+        let args = CliArgs::parse_from([
+            "workspacers-cli",
+            "--sort",
+            "manual",
+            "--picker",
+            "fzf",
+            "--log-level",
+            "warn",
+            "--json-file",
+            "/lists",
+        ]);
+        apply_args(&mut config, &args);
+        assert_eq!(config.sort_for("notes"), SortMode::Manual);
+        assert_eq!(config.picker, "fzf");
+        assert_eq!(config.log_level, "warn");
+        assert_eq!(config.data_dir, Some("/lists".into()));
+
+        // Without flags the config is kept
+        let mut unchanged = AppConfig::default();
+        apply_args(&mut unchanged, &CliArgs::parse_from(["workspacers-cli"]));
+        assert_eq!(unchanged.sort, SortMode::default());
+        assert_eq!(unchanged.picker, "native");
+    }
+}
@@ -0,0 +1,104 @@
+use common::json::{self, SCHEMA_VERSION};
+use std::path::Path;
+
+// Lines of unchanged context shown around each change
+const CONTEXT: usize = 2;
+
+// Shows the upgrade of the list to the current schema version, and writes it when `apply` is set
+pub fn run(json_file: &Path, apply: bool) -> Result<(), String> {
+    let migration = json::plan_migration(json_file).map_err(|err| err.to_string())?;
+    if !migration.is_needed() {
+        println!(
+            "'{}' is up to date (schema version {SCHEMA_VERSION})",
+            json_file.to_string_lossy()
+        );
+        return Ok(());
+    }
+
+    println!(
+        "'{}': schema version {} -> {SCHEMA_VERSION}",
+        json_file.to_string_lossy(),
+        migration.from_version
+    );
+    if let (Some(before), Some(after)) = (&migration.before, &migration.after) {
+        print!("{}", diff(before, after));
+    }
+
+    if !apply {
+        println!("Dry run, nothing written. Run again with --apply to upgrade the list");
+        return Ok(());
+    }
+    json::apply_migration(&migration).map_err(|err| format!("Could not migrate: {err}"))?;
+    println!(
+        "Upgraded, the previous list is kept in '{}'",
+        json::get_backup_file(json_file, 1).to_string_lossy()
+    );
+    Ok(())
+}
+
+enum Line<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+// Line diff of the longest common subsequence, printed as hunks with a few lines of context
+fn diff(before: &str, after: &str) -> String {
+    let old: Vec<&str> = before.lines().collect();
+    let new: Vec<&str> = after.lines().collect();
+
+    // lcs[i][j] is the length of the longest common subsequence of old[i..] and new[j..]
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = match old[i] == new[j] {
+                true => lcs[i + 1][j + 1] + 1,
+                false => lcs[i + 1][j].max(lcs[i][j + 1]),
+            };
+        }
+    }
+
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push(Line::Same(old[i]));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            lines.push(Line::Removed(old[i]));
+            i += 1;
+        } else {
+            lines.push(Line::Added(new[j]));
+            j += 1;
+        }
+    }
+
+    let changed: Vec<usize> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| !matches!(line, Line::Same(_)))
+        .map(|(idx, _)| idx)
+        .collect();
+    let mut output = String::new();
+    let mut shown_to = 0;
+    for (idx, line) in lines.iter().enumerate() {
+        let near_change = changed.iter().any(|&changed_idx| changed_idx.abs_diff(idx) <= CONTEXT);
+        if !near_change {
+            continue;
+        }
+        if idx > shown_to {
+            output.push_str("  ...\n");
+        }
+        shown_to = idx + 1;
+        match line {
+            Line::Same(text) => output.push_str(&format!("  {text}\n")),
+            Line::Removed(text) => output.push_str(&format!("- {text}\n")),
+            Line::Added(text) => output.push_str(&format!("+ {text}\n")),
+        }
+    }
+    if shown_to < lines.len() {
+        output.push_str("  ...\n");
+    }
+    output
+}
@@ -0,0 +1,140 @@
+use clap::ValueEnum;
+use common::json::Workspace;
+use serde::Serialize;
+use std::path::Path;
+
+// Undecorated output for scripts, instead of the formatted picker rows
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum OutputFormat {
+    /// A JSON array for lists, an object for a single workspace
+    Json,
+    /// One workspace per line: index, name, path, kind, exists, tags
+    Tsv,
+    /// One path per line
+    Plain,
+}
+
+#[derive(Serialize)]
+struct OutputEntry<'a> {
+    index: usize,
+    id: &'a str,
+    name: &'a str,
+    path: &'a str,
+    kind: &'a str,
+    exists: bool,
+    tags: &'a [String],
+}
+
+impl<'a> OutputEntry<'a> {
+    // `index` is the position in the list file, not the display order
+    fn new(index: usize, ws: &'a Workspace) -> OutputEntry<'a> {
+        let path = Path::new(&ws.path);
+        let is_dir = match path.exists() {
+            true => path.is_dir(),
+            false => ws.path.ends_with(['/', '\\']), // Best guess for paths that no longer exist
+        };
+        OutputEntry {
+            index,
+            id: &ws.id,
+            name: &ws.name,
+            path: &ws.path,
+            kind: if is_dir { "dir" } else { "file" },
+            exists: path.exists(),
+            tags: &ws.tags,
+        }
+    }
+
+    fn tsv(&self) -> String {
+        [
+            self.index.to_string(),
+            tsv_value(self.name),
+            tsv_value(self.path),
+            self.kind.to_string(),
+            self.exists.to_string(),
+            tsv_value(&self.tags.join(",")),
+        ]
+        .join("\t")
+    }
+}
+
+// Tabs and newlines would break the columns
+fn tsv_value(value: &str) -> String {
+    value.replace(['\t', '\n', '\r'], " ")
+}
+
+// Prints `shown` in order, each with its index in `workspaces`
+pub fn print_workspaces(workspaces: &[Workspace], shown: &[Workspace], format: OutputFormat) -> Result<(), String> {
+    let entries: Vec<OutputEntry> = shown
+        .iter()
+        .map(|ws| OutputEntry::new(index_of(workspaces, ws), ws))
+        .collect();
+    match format {
+        OutputFormat::Json => println!("{}", to_json(&entries)?),
+        OutputFormat::Tsv => entries.iter().for_each(|entry| println!("{}", entry.tsv())),
+        OutputFormat::Plain => entries.iter().for_each(|entry| println!("{}", entry.path)),
+    }
+    Ok(())
+}
+
+pub fn print_workspace(workspaces: &[Workspace], ws: &Workspace, format: OutputFormat) -> Result<(), String> {
+    let entry = OutputEntry::new(index_of(workspaces, ws), ws);
+    match format {
+        OutputFormat::Json => println!("{}", to_json(&entry)?),
+        OutputFormat::Tsv => println!("{}", entry.tsv()),
+        OutputFormat::Plain => println!("{}", entry.path),
+    }
+    Ok(())
+}
+
+fn index_of(workspaces: &[Workspace], ws: &Workspace) -> usize {
+    workspaces.iter().position(|w| w.id == ws.id).unwrap_or_default()
+}
+
+fn to_json<T: Serialize>(value: &T) -> Result<String, String> {
+    serde_json::to_string(value).map_err(|err| format!("Could not serialize: {err}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ws(id: &str, path: &str) -> Workspace {
+        let mut ws = Workspace::new(format!("ws\t{id}"), path.to_string());
+        ws.id = id.to_string();
+        ws
+    }
+
+    #[test]
+    fn entries_describe_the_path() {
+        let dir = env!("CARGO_MANIFEST_DIR");
+        let file = format!("{dir}/Cargo.toml");
+        let workspaces = vec![
+            ws("a", dir),
+            ws("b", &file),
+            ws("c", "/no/such/dir/"),
+            ws("d", "/no/such/file"),
+        ];
+        let entries: Vec<(&str, bool)> = workspaces
+            .iter()
+            .map(|ws| OutputEntry::new(0, ws))
+            .map(|entry| (entry.kind, entry.exists))
+            .collect();
+        assert_eq!(
+            entries,
+            vec![("dir", true), ("file", true), ("dir", false), ("file", false)]
+        );
+        assert_eq!(index_of(&workspaces, &workspaces[2]), 2);
+    }
+
+    #[test]
+    fn entries_serialize_to_json_and_tsv() {
+        let mut ws = ws("a", "/no/such/file");
+        ws.tags = vec!["work".to_string(), "rust".to_string()];
+        let entry = OutputEntry::new(3, &ws);
+        assert_eq!(
+            to_json(&entry).unwrap(),
+            r#"{"index":3,"id":"a","name":"ws\ta","path":"/no/such/file","kind":"file","exists":false,"tags":["work","rust"]}"#
+        );
+        assert_eq!(entry.tsv(), "3\tws a\t/no/such/file\tfile\tfalse\twork,rust");
+    }
+}
@@ -1,40 +1,165 @@
-use common::json::Workspace;
+use clap::ValueEnum;
+use common::json::{self, Workspace};
+use common::sort::{self, SortMode};
+use common::store::WorkspaceStore;
+use common::template::Template;
 use fzf_wrapped::{Fzf, Layout};
 use log::info;
 
+use crate::{fuzzy_picker, picker_actions};
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default)]
+pub enum PickerBackend {
+    /// Built in picker, no external dependencies
+    #[default]
+    Native,
+    /// Requires fzf on the PATH
+    Fzf,
+}
+
+// Same keys as the Telescope picker
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PickerAction {
+    Delete,
+    Edit,
+    Promote,
+    Demote,
+    Add,
+}
+
+// (action, fzf key name)
+pub const ACTION_KEYS: [(PickerAction, &str); 5] = [
+    (PickerAction::Delete, "ctrl-x"),
+    (PickerAction::Edit, "ctrl-e"),
+    (PickerAction::Promote, "ctrl-u"),
+    (PickerAction::Demote, "ctrl-d"),
+    (PickerAction::Add, "ctrl-a"),
+];
+
+const HEADER: &str = "Workspace: (ctrl-x delete, ctrl-e edit, ctrl-u/ctrl-d move, ctrl-a add)";
+
+// Result of a single run of a picker, indexes are into the rows it was given
+pub enum Picked {
+    Selected(usize),
+    Action(PickerAction, Option<usize>),
+    Cancelled,
+}
+
+pub struct PickerOptions<'a> {
+    pub backend: PickerBackend,
+    pub tags: &'a [String],
+    pub sort: SortMode,
+    pub fzf_args: &'a [String],
+    pub template: &'a Template,
+}
+
+// Each fzf line is "<id>\t<fmt>", only the fmt is shown and the id is used to find the selection
+const FZF_DELIMITER: char = '\t';
+
 // Returns an Option Some Workspace or None exited safely
-pub fn pick_workspace(workspaces: Vec<Workspace>) -> Result<Option<Workspace>, String> {
-    let fmt_vals = common::formatter::fmt(&workspaces);
-    let vals: Vec<String> = fmt_vals.iter().map(|(ws_str, _)| ws_str.to_string()).collect();
+// Actions update the list and reopen the picker on the same workspace
+pub fn pick_workspace(store: &mut WorkspaceStore, opts: &PickerOptions) -> Result<Option<Workspace>, String> {
+    let mut selected_id: Option<String> = None;
+    let mut message: Option<String> = None;
+    loop {
+        let shown = sort::sorted(&json::filter_by_tags(store.workspaces(), opts.tags), opts.sort);
+        if shown.is_empty() {
+            eprintln!("No workspaces left to pick from");
+            return Ok(None);
+        }
+        let selected = selected_id
+            .as_ref()
+            .and_then(|id| json::position_by_id(&shown, id))
+            .unwrap_or(0);
 
-    match run_fzf(vals)? {
-        None => return Ok(None), // If user cancelled, do not error
+        let header = match &message {
+            Some(message) => format!("{HEADER}\n{message}"),
+            None => HEADER.to_string(),
+        };
+        let picked = match opts.backend {
+            PickerBackend::Native => pick_native(&shown, selected, &header, opts.template)?,
+            PickerBackend::Fzf => pick_fzf(&shown, selected, &header, opts)?,
+        };
+
+        match picked {
+            Picked::Selected(idx) => return Ok(Some(shown[idx].clone())),
+            Picked::Cancelled => return Ok(None),
+            Picked::Action(action, idx) => {
+                info!("Picker action: {action:?} on {idx:?}");
+                match picker_actions::run(action, store, &shown, idx, opts.sort) {
+                    Ok(id) => {
+                        selected_id = id;
+                        message = None;
+                    }
+                    Err(err) => {
+                        selected_id = idx.map(|idx| shown[idx].id.to_string());
+                        message = Some(err);
+                    }
+                }
+                // Pick up changes from other processes too
+                store.reload().map_err(|err| err.to_string())?;
+            }
+        }
+    }
+}
+
+fn pick_native(workspaces: &[Workspace], selected: usize, header: &str, template: &Template) -> Result<Picked, String> {
+    let rows: Vec<String> = common::formatter::fmt(workspaces, template)
+        .into_iter()
+        .map(|(ws_str, _)| ws_str)
+        .collect();
+    fuzzy_picker::pick(&rows, selected, header)
+}
+
+fn pick_fzf(workspaces: &[Workspace], selected: usize, header: &str, opts: &PickerOptions) -> Result<Picked, String> {
+    let vals: Vec<String> = common::formatter::fmt(workspaces, opts.template)
+        .iter()
+        .map(|(ws_str, ws)| format!("{}{FZF_DELIMITER}{ws_str}", ws.id))
+        .collect();
+
+    match run_fzf(vals, selected, header, opts.fzf_args)? {
+        None => Ok(Picked::Cancelled), // If user cancelled, do not error
         Some(fzf_output) => {
-            let lines = filter_result(fzf_output);
-            if lines.is_empty() {
+            // With --expect the first line is the key pressed, empty for enter, then the selection
+            let mut lines = fzf_output.lines();
+            let key = lines.next().unwrap_or_default().trim();
+            let Some(line) = lines.next().filter(|line| !line.trim().is_empty()) else {
                 info!("No lines returned from fzf");
-                return Ok(None);
-            }
+                return Ok(Picked::Cancelled);
+            };
 
-            let ws_match = fmt_vals
-                .iter()
-                .find(|ws| ws.0 == lines[0])
-                .ok_or_else(|| "Could not match fmt back to Workspace".to_string())?;
+            let id = line
+                .split(FZF_DELIMITER)
+                .next()
+                .ok_or_else(|| "Could not read id from fzf selection".to_string())?;
+            let idx = json::position_by_id(workspaces, id)
+                .ok_or_else(|| format!("Could not match id back to Workspace: '{id}'"))?;
 
-            match lines.len() {
-                1 => Ok(Some(ws_match.1.clone())), // This function expects a new WS for adding so clone to allow
-                _ => Err("Unexpected number of args".to_string()),
+            match ACTION_KEYS.iter().find(|(_, action_key)| *action_key == key) {
+                Some((action, _)) => Ok(Picked::Action(*action, Some(idx))),
+                None => Ok(Picked::Selected(idx)),
             }
         }
     }
 }
 
-fn run_fzf(values: Vec<String>) -> Result<Option<String>, String> {
+fn run_fzf(values: Vec<String>, selected: usize, header: &str, fzf_args: &[String]) -> Result<Option<String>, String> {
+    let expect_keys: Vec<&str> = ACTION_KEYS.iter().map(|(_, key)| *key).collect();
     let mut fzf = Fzf::builder()
         .layout(Layout::Reverse)
-        .header("Workspace:")
+        .header(header)
+        .custom_args(
+            [
+                format!("--delimiter={FZF_DELIMITER}"),
+                "--with-nth=2..".to_string(),
+                format!("--expect={}", expect_keys.join(",")),
+                format!("--bind=load:pos({})", selected + 1), // pos is 1 based
+            ]
+            .into_iter()
+            .chain(fzf_args.iter().cloned()), // From the config, after ours so they can override them
+        )
         .build()
-        .or_else(|err| Err(format!("fzf - Could not build: {err}")))?;
+        .map_err(|err| format!("fzf - Could not build: {err}"))?;
 
     match fzf.run() {
         Ok(()) => {
@@ -48,13 +173,3 @@ fn run_fzf(values: Vec<String>) -> Result<Option<String>, String> {
         Err(e) => Err(format!("fzf - could not run: {e}")),
     }
 }
-
-fn filter_result(output: String) -> Vec<String> {
-    output
-        .split('\n')
-        .map(|s| s.trim())
-        .filter(|s| !s.is_empty())
-        .rev() // Reverse to get selected first
-        .map(String::from)
-        .collect()
-}
@@ -0,0 +1,73 @@
+use common::formatter;
+use common::json::Workspace;
+use common::sort::SortMode;
+use common::store::WorkspaceStore;
+
+use crate::add_workspace;
+use crate::picker::PickerAction;
+
+// Runs an action from the picker on the workspace at `idx` in `shown`
+// Returns the id of the workspace to select when the picker reopens, Err is shown in the picker
+pub fn run(
+    action: PickerAction,
+    store: &mut WorkspaceStore,
+    shown: &[Workspace],
+    idx: Option<usize>,
+    sort_mode: SortMode,
+) -> Result<Option<String>, String> {
+    // Every action but add works on the selected workspace
+    let target = || {
+        idx.and_then(|idx| shown.get(idx))
+            .ok_or_else(|| "No selected Workspace".to_string())
+    };
+
+    match action {
+        PickerAction::Add => {
+            let new_ws = add_workspace::read_new_workspace(store.workspaces())?;
+            let id = new_ws.id.to_string();
+            store.add(new_ws).map_err(|err| err.to_string())?;
+            Ok(Some(id))
+        }
+        PickerAction::Delete => {
+            let target = target()?;
+            let answer = add_workspace::read_line(format!("'y' to delete '{}'", target.name), "")?;
+            if !answer.eq_ignore_ascii_case("y") {
+                return Ok(Some(target.id.to_string()));
+            }
+            store.remove(&target.id).map_err(|err| err.to_string())?;
+            // Select the workspace that took its place
+            let next = idx.and_then(|idx| {
+                shown
+                    .get(idx + 1)
+                    .or_else(|| idx.checked_sub(1).and_then(|i| shown.get(i)))
+            });
+            Ok(next.map(|ws| ws.id.to_string()))
+        }
+        PickerAction::Edit => {
+            let target = target()?;
+            let name = add_workspace::read_line("New Name".to_string(), &target.name)?;
+            let path = add_workspace::read_line("New Path".to_string(), &target.path)?;
+            // Resolved the same way as a new workspace
+            let path = add_workspace::absolute_path(formatter::unfmt_path(path))?;
+            store
+                .update(&target.id, |ws| {
+                    ws.name = name;
+                    ws.path = path;
+                })
+                .map_err(|err| err.to_string())?;
+            Ok(Some(target.id.to_string()))
+        }
+        PickerAction::Promote | PickerAction::Demote => {
+            let target = target()?;
+            if sort_mode != SortMode::Manual {
+                return Err(format!("Cannot reorder a list sorted by {sort_mode}"));
+            }
+            match action {
+                PickerAction::Promote => store.promote(&target.id),
+                _ => store.demote(&target.id),
+            }
+            .map_err(|err| err.to_string())?;
+            Ok(Some(target.id.to_string()))
+        }
+    }
+}
@@ -0,0 +1,2 @@
+
+bind -x '"__WS_KEY__": __WS_CMD__'
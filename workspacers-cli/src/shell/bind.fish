@@ -0,0 +1,2 @@
+
+bind __WS_KEY__ '__WS_CMD__; commandline -f repaint'
@@ -0,0 +1,5 @@
+
+Set-PSReadLineKeyHandler -Chord '__WS_KEY__' -ScriptBlock {
+    __WS_CMD__
+    [Microsoft.PowerShell.PSConsoleReadLine]::InvokePrompt()
+}
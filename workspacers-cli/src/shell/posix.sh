@@ -0,0 +1,44 @@
+# workspacers shell integration, generated by `__WS_EXE__ init`
+# Opens the picked workspace: directories are cd'd into, files opened with $EDITOR
+
+__WS_CMD__() {
+    case "$1" in
+        -a | --add)
+            # Add prompts for input so can't have its output captured
+            command __WS_EXE__ "$@"
+            return
+            ;;
+        -j | --print-json)
+            ${EDITOR:-vi} "$(command __WS_EXE__ "$@")"
+            return
+            ;;
+    esac
+    local ws_arg ws_path ws_skip=
+    for ws_arg in "$@"; do
+        if [ -n "$ws_skip" ]; then
+            ws_skip=
+            continue
+        fi
+        case "$ws_arg" in
+            __WS_VALUE_FLAGS__)
+                # The next argument is the flag's value, e.g. a list named like a subcommand
+                ws_skip=1
+                ;;
+            __WS_SUBCOMMANDS__)
+                command __WS_EXE__ "$@"
+                return
+                ;;
+        esac
+    done
+
+    ws_path="$(command __WS_EXE__ "$@")" || return
+    [ -z "$ws_path" ] && return 0
+    if [ -d "$ws_path" ]; then
+        cd -- "$ws_path" || return
+    elif [ -e "$ws_path" ]; then
+        ${EDITOR:-vi} "$ws_path"
+    else
+        echo "Path does not exist: $ws_path" >&2
+        return 1
+    fi
+}
@@ -0,0 +1,40 @@
+# workspacers shell integration, generated by `__WS_EXE__ init fish`
+# Opens the picked workspace: directories are cd'd into, files opened with $EDITOR
+
+function __WS_CMD__
+    set -l editor vi
+    set -q EDITOR; and set editor $EDITOR
+
+    switch "$argv[1]"
+        case -a --add
+            # Add prompts for input so can't have its output captured
+            command __WS_EXE__ $argv
+            return
+        case -j --print-json
+            $editor (command __WS_EXE__ $argv)
+            return
+    end
+    set -l skip 0
+    for arg in $argv
+        if test $skip = 1
+            set skip 0
+        else if contains -- $arg __WS_VALUE_FLAGS__
+            # The next argument is the flag's value, e.g. a list named like a subcommand
+            set skip 1
+        else if contains -- $arg __WS_SUBCOMMANDS__
+            command __WS_EXE__ $argv
+            return
+        end
+    end
+
+    set -l ws_path (command __WS_EXE__ $argv); or return
+    test -z "$ws_path"; and return 0
+    if test -d "$ws_path"
+        cd $ws_path
+    else if test -e "$ws_path"
+        $editor $ws_path
+    else
+        echo "Path does not exist: $ws_path" >&2
+        return 1
+    end
+end
@@ -0,0 +1,41 @@
+# workspacers shell integration, generated by `__WS_EXE__ init powershell`
+# Opens the picked workspace: directories are cd'd into, files opened with $env:EDITOR
+
+function __WS_CMD__ {
+    $editor = if ($env:EDITOR) { $env:EDITOR } elseif ($IsWindows -or $env:OS -eq "Windows_NT") { "notepad" } else { "vi" }
+
+    if ($args[0] -in @("-a", "--add")) {
+        # Add prompts for input so can't have its output captured
+        & __WS_EXE__ @args
+        return
+    }
+    if ($args[0] -in @("-j", "--print-json")) {
+        & $editor (& __WS_EXE__ @args)
+        return
+    }
+    $skip = $false
+    foreach ($arg in $args) {
+        if ($skip) {
+            $skip = $false
+        } elseif ($arg -cin @(__WS_VALUE_FLAGS__)) {
+            # The next argument is the flag's value, e.g. a list named like a subcommand
+            $skip = $true
+        } elseif ($arg -in @(__WS_SUBCOMMANDS__)) {
+            & __WS_EXE__ @args
+            return
+        }
+    }
+
+    $wsPath = & __WS_EXE__ @args
+    if ($LASTEXITCODE -ne 0 -or -not $wsPath) {
+        return
+    }
+    $wsPath = "$wsPath".Trim()
+    if (Test-Path -Path $wsPath -PathType Container) {
+        Set-Location $wsPath
+    } elseif (Test-Path -Path $wsPath -PathType Leaf) {
+        & $editor $wsPath
+    } else {
+        Write-Host "Path does not exist: $wsPath"
+    }
+}
@@ -0,0 +1,115 @@
+use clap::ValueEnum;
+
+// Placeholders filled in the templates under ./shell
+const EXE: &str = "__WS_EXE__";
+const CMD: &str = "__WS_CMD__";
+const SUBCOMMANDS: &str = "__WS_SUBCOMMANDS__";
+const VALUE_FLAGS: &str = "__WS_VALUE_FLAGS__";
+const KEY: &str = "__WS_KEY__";
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    #[value(alias = "nu")]
+    Nushell,
+    #[value(alias = "pwsh")]
+    Powershell,
+}
+
+// Names the script needs from the CLI's arguments
+pub struct CliNames<'a> {
+    // Passed straight through rather than treated as a pick
+    pub subcommands: &'a [String],
+    // Flags taking a value, so a value named like a subcommand isn't taken for one
+    pub value_flags: &'a [String],
+}
+
+// Returns the integration script for the shell, `exe` is called for everything so each shell
+// only has to act on the path printed by the picker
+pub fn init(shell: Shell, exe: &str, cmd: &str, bind: Option<&str>, names: &CliNames) -> Result<String, String> {
+    let (function, bind_template) = match shell {
+        Shell::Bash => (include_str!("shell/posix.sh"), include_str!("shell/bind.bash")),
+        Shell::Zsh => (include_str!("shell/posix.sh"), include_str!("shell/bind.zsh")),
+        Shell::Fish => (include_str!("shell/ws.fish"), include_str!("shell/bind.fish")),
+        Shell::Nushell => (include_str!("shell/ws.nu"), include_str!("shell/bind.nu")),
+        Shell::Powershell => (include_str!("shell/ws.ps1"), include_str!("shell/bind.ps1")),
+    };
+
+    let mut script = function.to_string();
+    if let Some(bind) = bind {
+        script.push_str(&bind_template.replace(KEY, &key_binding(shell, bind)?));
+    }
+    Ok(script
+        .replace(SUBCOMMANDS, &pattern(shell, names.subcommands))
+        .replace(VALUE_FLAGS, &pattern(shell, names.value_flags))
+        .replace(EXE, exe)
+        .replace(CMD, cmd))
+}
+
+// The words as each shell's list or case pattern
+fn pattern(shell: Shell, words: &[String]) -> String {
+    match shell {
+        Shell::Bash | Shell::Zsh => words.join(" | "),
+        Shell::Fish => words.join(" "),
+        Shell::Nushell => words.iter().map(|s| format!("\"{s}\"")).collect::<Vec<_>>().join(" "),
+        Shell::Powershell => words.iter().map(|s| format!("\"{s}\"")).collect::<Vec<_>>().join(", "),
+    }
+}
+
+// Keys are given as ctrl-<letter> and converted to each shell's notation
+fn key_binding(shell: Shell, bind: &str) -> Result<String, String> {
+    let key = bind
+        .to_lowercase()
+        .strip_prefix("ctrl-")
+        .and_then(|key| {
+            let mut chars = key.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) if c.is_ascii_lowercase() => Some(c),
+                _ => None,
+            }
+        })
+        .ok_or_else(|| format!("Unsupported key binding: '{bind}' (expected ctrl-<letter>, e.g. ctrl-g)"))?;
+
+    Ok(match shell {
+        Shell::Bash => format!("\\C-{key}"),
+        Shell::Zsh => format!("^{}", key.to_ascii_uppercase()),
+        Shell::Fish => format!("\\c{key}"),
+        Shell::Nushell => format!("char_{key}"),
+        Shell::Powershell => format!("Ctrl+{key}"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_use_each_shells_notation() {
+        let keys: Vec<String> = Shell::value_variants()
+            .iter()
+            .map(|shell| key_binding(*shell, "Ctrl-G").unwrap())
+            .collect();
+        assert_eq!(keys, ["\\C-g", "^G", "\\cg", "char_g", "Ctrl+g"]);
+        for bind in ["g", "alt-g", "ctrl-", "ctrl-gg", "ctrl-1"] {
+            assert!(key_binding(Shell::Bash, bind).is_err(), "{bind}");
+        }
+    }
+
+    #[test]
+    fn scripts_have_no_placeholders_left() {
+        let subcommands = ["list".to_string(), "path".to_string()];
+        let value_flags = ["-n".to_string(), "--name".to_string()];
+        let names = CliNames {
+            subcommands: &subcommands,
+            value_flags: &value_flags,
+        };
+        for shell in Shell::value_variants() {
+            let script = init(*shell, "workspacers-cli", "ws", Some("ctrl-g"), &names).unwrap();
+            assert!(!script.contains("__WS_"), "{shell:?}");
+            assert!(script.contains("--name") && script.contains("path"), "{shell:?}");
+        }
+        assert!(init(Shell::Fish, "workspacers-cli", "ws", Some("ctrl-1"), &names).is_err());
+    }
+}
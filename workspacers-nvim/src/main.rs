@@ -1,6 +1,8 @@
-use clap::{Parser, command};
+use clap::Parser;
 use common::json;
+use common::setup::config::get_config;
 use common::setup::logging::setup_logger;
+use common::template::Template;
 use log::{error, info};
 use nvim_rs::create::tokio as create;
 use std::path::PathBuf;
@@ -10,7 +12,9 @@ use std::{
 };
 use tokio::runtime;
 
+mod rpc_args;
 mod rpc_commands;
+mod watcher;
 
 #[derive(Parser, Debug)]
 #[command(long_about = None)]
@@ -20,6 +24,10 @@ struct NvimArgs {
 
     #[arg(long = "config-file")]
     config_file: Option<PathBuf>,
+
+    /// off | error | warn | info | debug | trace, overrides log_level in the config
+    #[arg(long = "log-level")]
+    log_level: Option<String>,
 }
 
 fn main() -> Result<(), Error> {
@@ -37,10 +45,30 @@ fn main() -> Result<(), Error> {
 }
 
 async fn run_workspacers(args: NvimArgs) -> Result<(), Error> {
-    let log_file = setup_logger()?;
-    let json_dir = json::get_json_dir(args.json_dir)?;
+    let (config_file, mut config) = get_config(args.config_file, json::APP_NAME)?;
+    // Flags override the config file and environment
+    if let Some(json_dir) = args.json_dir {
+        config.data_dir = Some(json_dir);
+    }
+    if let Some(log_level) = args.log_level {
+        config.log_level = log_level;
+    }
+    let log_file = setup_logger(config.log_level_filter()?)?;
+    info!("Using config: {config_file}");
+    let json_dir = json::get_json_dir(config.data_dir.clone())?;
     info!("Using json dir: {}", json_dir.to_string_lossy());
-    let (nvim, io_handler) = create::new_parent(rpc_commands::NeovimHandler { log_file, json_dir }).await;
+    let template = Template::from_config(&config).map_err(|err| Error::new(ErrorKind::InvalidData, err))?;
+    let (nvim, io_handler) = create::new_parent(rpc_commands::NeovimHandler {
+        log_file,
+        json_dir: json_dir.clone(),
+        config,
+        template,
+    })
+    .await;
+    // Lists still work without live updates, so a watcher that can't start is only logged
+    let _watcher = watcher::watch(&json_dir, nvim.clone())
+        .inspect_err(|err| error!("Could not watch {}: {err}", json_dir.to_string_lossy()))
+        .ok();
     match io_handler.await {
         Ok(_) => {
             info!("App Completed. Closing");
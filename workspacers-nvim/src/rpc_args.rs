@@ -0,0 +1,547 @@
+use std::fmt;
+
+use common::json::ReadError;
+use common::session::Session;
+use common::setup::config::AppConfig;
+use common::sort::SortMode;
+use common::store::StoreError;
+use nvim_rs::Value;
+use serde::Serialize;
+
+use crate::rpc_commands;
+
+// Kind of failure, for lua to tell bad requests from failures reading or writing the list
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    UnknownMethod,
+    MissingArgument,
+    InvalidArgument,
+    NotFound,
+    Invalid,
+    Conflict,
+    ReadFailed,
+    WriteFailed,
+}
+
+// Error reply sent to lua, as JSON so lua gets every field from the error of vim.rpcrequest
+#[derive(Debug, Serialize)]
+pub struct RpcError {
+    pub code: ErrorCode,
+    pub message: String,
+    // The argument that was missing or invalid, e.g. "arg[1] workspace.path"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+}
+
+impl RpcError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> RpcError {
+        RpcError {
+            code,
+            message: message.into(),
+            field: None,
+        }
+    }
+
+    fn arg(code: ErrorCode, field: &str, message: String) -> RpcError {
+        RpcError {
+            code,
+            message,
+            field: Some(field.to_string()),
+        }
+    }
+
+    pub fn to_value(&self) -> Value {
+        let json = serde_json::to_string(self).unwrap_or_else(|_| self.message.to_string());
+        Value::String(json.into())
+    }
+}
+
+impl fmt::Display for RpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.field {
+            Some(field) => write!(f, "{} ({field})", self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl From<ReadError> for RpcError {
+    fn from(err: ReadError) -> RpcError {
+        RpcError::new(ErrorCode::ReadFailed, err.to_string())
+    }
+}
+
+impl From<StoreError> for RpcError {
+    fn from(err: StoreError) -> RpcError {
+        let code = match &err {
+            StoreError::Read(_) => ErrorCode::ReadFailed,
+            StoreError::Lock(..) | StoreError::Write(..) => ErrorCode::WriteFailed,
+            StoreError::NotFound(_) => ErrorCode::NotFound,
+            StoreError::Invalid(_) | StoreError::OutOfRange { .. } => ErrorCode::Invalid,
+            StoreError::Conflict(_) => ErrorCode::Conflict,
+        };
+        RpcError::new(code, err.to_string())
+    }
+}
+
+// Sort mode for every list, or per list name
+#[derive(Debug, Default, PartialEq)]
+pub enum SortArg {
+    #[default]
+    Config,
+    All(SortMode),
+    PerList(Vec<(String, SortMode)>),
+}
+
+impl SortArg {
+    // Lists without a mode use the sort of the list from the config
+    pub fn for_list(&self, list_name: &str, config: &AppConfig) -> SortMode {
+        let default_sort = config.sort_for(list_name);
+        match self {
+            SortArg::Config => default_sort,
+            SortArg::All(mode) => *mode,
+            SortArg::PerList(modes) => modes
+                .iter()
+                .find(|(name, _)| name == list_name)
+                .map(|(_, mode)| *mode)
+                .unwrap_or(default_sort),
+        }
+    }
+}
+
+// Every request the binary handles, a method missing from ALL is never constructed so fails the dead code lint
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Method {
+    Hello,
+    Config,
+    ListAll,
+    Json,
+    Current,
+    List,
+    Record,
+    Add,
+    Delete,
+    Promote,
+    Demote,
+    Replace,
+    Opened,
+    AddTag,
+    RemoveTag,
+    SaveSession,
+    LoadSession,
+}
+
+impl Method {
+    pub const ALL: [(Method, &str); 17] = [
+        (Method::Hello, rpc_commands::RPC_WS_HELLO),
+        (Method::Config, rpc_commands::RPC_WS_CONFIG),
+        (Method::ListAll, rpc_commands::RPC_WS_LIST_ALL),
+        (Method::Json, rpc_commands::RPC_WS_JSON),
+        (Method::Current, rpc_commands::RPC_WS_CURRENT),
+        (Method::List, rpc_commands::RPC_WS_LIST),
+        (Method::Record, rpc_commands::RPC_WS_RECORD),
+        (Method::Add, rpc_commands::RPC_WS_ADD),
+        (Method::Delete, rpc_commands::RPC_WS_DELETE),
+        (Method::Promote, rpc_commands::RPC_WS_PROMOTE),
+        (Method::Demote, rpc_commands::RPC_WS_DEMOTE),
+        (Method::Replace, rpc_commands::RPC_WS_REPLACE),
+        (Method::Opened, rpc_commands::RPC_WS_OPENED),
+        (Method::AddTag, rpc_commands::RPC_WS_ADD_TAG),
+        (Method::RemoveTag, rpc_commands::RPC_WS_REMOVE_TAG),
+        (Method::SaveSession, rpc_commands::RPC_WS_SAVE_SESSION),
+        (Method::LoadSession, rpc_commands::RPC_WS_LOAD_SESSION),
+    ];
+
+    fn parse(name: &str) -> Option<Method> {
+        Method::ALL.iter().find(|(_, n)| *n == name).map(|(method, _)| *method)
+    }
+}
+
+// A request decoded from its method name and args
+#[derive(Debug, PartialEq)]
+pub enum Request {
+    // Protocol version of the plugin, if sent
+    Hello {
+        client: Option<String>,
+    },
+    Config,
+    ListAll {
+        sort: SortArg,
+        tags: Vec<String>,
+    },
+    Json,
+    // Path to find the workspace of, such as the cwd or a buffer
+    Current {
+        path: String,
+    },
+    List {
+        list: String,
+        sort: SortArg,
+        tags: Vec<String>,
+    },
+    Record {
+        list: String,
+        id: String,
+    },
+    Add {
+        list: String,
+        name: String,
+        path: String,
+    },
+    Delete {
+        list: String,
+        id: String,
+    },
+    Promote {
+        list: String,
+        id: String,
+    },
+    Demote {
+        list: String,
+        id: String,
+    },
+    Replace {
+        list: String,
+        id: String,
+        name: String,
+        path: String,
+    },
+    Opened {
+        list: String,
+        id: String,
+    },
+    AddTag {
+        list: String,
+        id: String,
+        tag: String,
+    },
+    RemoveTag {
+        list: String,
+        id: String,
+        tag: String,
+    },
+    // Sessions are keyed by list and id, as the same id can be in more than one list
+    SaveSession {
+        list: String,
+        id: String,
+        session: Session,
+    },
+    LoadSession {
+        list: String,
+        id: String,
+    },
+}
+
+impl Request {
+    pub fn decode(name: &str, args: &[Value]) -> Result<Request, RpcError> {
+        let Some(method) = Method::parse(name) else {
+            return Err(RpcError::new(
+                ErrorCode::UnknownMethod,
+                format!("Unknown request: {name}"),
+            ));
+        };
+        let args = Args(args);
+        let request = match method {
+            Method::Hello => Request::Hello {
+                client: args.optional_string(0, "protocol")?,
+            },
+            Method::Config => Request::Config,
+            Method::ListAll => Request::ListAll {
+                sort: args.sort(0)?,
+                tags: args.tags(1)?,
+            },
+            Method::Json => Request::Json,
+            Method::Current => Request::Current {
+                path: args.string(0, "path")?,
+            },
+            Method::List => Request::List {
+                list: args.string(0, "list")?,
+                sort: args.sort(1)?,
+                tags: args.tags(2)?,
+            },
+            Method::Record => Request::Record {
+                list: args.string(0, "list")?,
+                id: args.string(1, "id")?,
+            },
+            Method::Add => {
+                let ws = args.map(1, "workspace")?;
+                Request::Add {
+                    list: args.string(0, "list")?,
+                    name: ws.string("name")?,
+                    path: ws.string("path")?,
+                }
+            }
+            Method::Delete => Request::Delete {
+                list: args.string(0, "list")?,
+                id: args.string(1, "id")?,
+            },
+            Method::Promote => Request::Promote {
+                list: args.string(0, "list")?,
+                id: args.string(1, "id")?,
+            },
+            Method::Demote => Request::Demote {
+                list: args.string(0, "list")?,
+                id: args.string(1, "id")?,
+            },
+            Method::Replace => {
+                let replace = args.map(1, "replace")?;
+                let new = replace.map("New")?;
+                Request::Replace {
+                    list: args.string(0, "list")?,
+                    id: replace.string("Key")?,
+                    name: new.string("Name")?,
+                    path: new.string("Path")?,
+                }
+            }
+            Method::Opened => Request::Opened {
+                list: args.string(0, "list")?,
+                id: args.string(1, "id")?,
+            },
+            Method::AddTag => Request::AddTag {
+                list: args.string(0, "list")?,
+                id: args.string(1, "id")?,
+                tag: args.string(2, "tag")?,
+            },
+            Method::RemoveTag => Request::RemoveTag {
+                list: args.string(0, "list")?,
+                id: args.string(1, "id")?,
+                tag: args.string(2, "tag")?,
+            },
+            Method::SaveSession => Request::SaveSession {
+                list: args.string(0, "list")?,
+                id: args.string(1, "id")?,
+                session: args.session(2)?,
+            },
+            Method::LoadSession => Request::LoadSession {
+                list: args.string(0, "list")?,
+                id: args.string(1, "id")?,
+            },
+        };
+        Ok(request)
+    }
+}
+
+struct Args<'a>(&'a [Value]);
+
+impl Args<'_> {
+    fn optional_string(&self, idx: usize, name: &str) -> Result<Option<String>, RpcError> {
+        match self.0.get(idx) {
+            None | Some(Value::Nil) => Ok(None),
+            Some(value) => as_string(value, &format!("arg[{idx}] {name}")).map(Some),
+        }
+    }
+
+    fn string(&self, idx: usize, name: &str) -> Result<String, RpcError> {
+        let field = format!("arg[{idx}] {name}");
+        match self.0.get(idx) {
+            None | Some(Value::Nil) => Err(missing(&field)),
+            Some(value) => as_string(value, &field),
+        }
+    }
+
+    fn map(&self, idx: usize, name: &str) -> Result<MapArg<'_>, RpcError> {
+        MapArg::new(self.0.get(idx), format!("arg[{idx}] {name}"))
+    }
+
+    // Either a mode for every list: "frecency" or a mode per list name: { "work": "frecency", "notes": "manual" }
+    fn sort(&self, idx: usize) -> Result<SortArg, RpcError> {
+        let field = format!("arg[{idx}] sort");
+        let parse = |mode: &Value, field: &str| -> Result<SortMode, RpcError> {
+            as_string(mode, field)?
+                .parse()
+                .map_err(|err: String| RpcError::arg(ErrorCode::InvalidArgument, field, err))
+        };
+        match self.0.get(idx) {
+            None | Some(Value::Nil) => Ok(SortArg::Config),
+            Some(Value::Map(modes)) => modes
+                .iter()
+                .map(|(list, mode)| {
+                    let list = as_string(list, &field)?;
+                    let mode = parse(mode, &format!("{field}.{list}"))?;
+                    Ok((list, mode))
+                })
+                .collect::<Result<_, _>>()
+                .map(SortArg::PerList),
+            Some(mode) => parse(mode, &field).map(SortArg::All),
+        }
+    }
+
+    // { Cwd = "...", Buffers = { "..." }, Layout = { Row = { { Leaf = { Path = "...", Line = 1, ... } }, ... } } }
+    // See `common::session::Session`
+    fn session(&self, idx: usize) -> Result<Session, RpcError> {
+        let field = format!("arg[{idx}] session");
+        let value = MapArg::new(self.0.get(idx), field.to_string())?;
+        let json = to_json(&Value::Map(value.entries.to_vec())).ok_or_else(|| {
+            RpcError::arg(
+                ErrorCode::InvalidArgument,
+                &field,
+                format!("Unsupported value in {field}"),
+            )
+        })?;
+        serde_json::from_value(json)
+            .map_err(|err| RpcError::arg(ErrorCode::InvalidArgument, &field, format!("Invalid {field}: {err}")))
+    }
+
+    // Either a single tag: "service" or workspaces having all of the tags: [ "service", "client" ]
+    fn tags(&self, idx: usize) -> Result<Vec<String>, RpcError> {
+        let field = format!("arg[{idx}] tags");
+        match self.0.get(idx) {
+            None | Some(Value::Nil) => Ok(Vec::new()),
+            Some(Value::Array(tags)) => tags.iter().map(|tag| as_string(tag, &field)).collect(),
+            Some(tag) => Ok(vec![as_string(tag, &field)?]),
+        }
+    }
+}
+
+// A table argument, keeping its field name for the errors of its entries
+struct MapArg<'a> {
+    entries: &'a [(Value, Value)],
+    field: String,
+}
+
+impl<'a> MapArg<'a> {
+    fn new(value: Option<&'a Value>, field: String) -> Result<MapArg<'a>, RpcError> {
+        match value {
+            None | Some(Value::Nil) => Err(missing(&field)),
+            Some(Value::Map(entries)) => Ok(MapArg { entries, field }),
+            Some(value) => Err(wrong_type(&field, "a table", value)),
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<&'a Value> {
+        self.entries
+            .iter()
+            .find(|(k, _)| k.as_str() == Some(key))
+            .map(|(_, v)| v)
+    }
+
+    fn string(&self, key: &str) -> Result<String, RpcError> {
+        let field = format!("{}.{key}", self.field);
+        match self.get(key) {
+            None | Some(Value::Nil) => Err(missing(&field)),
+            Some(value) => as_string(value, &field),
+        }
+    }
+
+    fn map(&self, key: &str) -> Result<MapArg<'a>, RpcError> {
+        MapArg::new(self.get(key), format!("{}.{key}", self.field))
+    }
+}
+
+// None for values JSON can't hold, such as binary or non string keys
+fn to_json(value: &Value) -> Option<serde_json::Value> {
+    Some(match value {
+        Value::Nil => serde_json::Value::Null,
+        Value::Boolean(b) => serde_json::Value::Bool(*b),
+        Value::Integer(n) => match n.as_u64() {
+            Some(n) => n.into(),
+            None => n.as_i64()?.into(),
+        },
+        Value::F32(n) => serde_json::Number::from_f64(f64::from(*n))?.into(),
+        Value::F64(n) => serde_json::Number::from_f64(*n)?.into(),
+        Value::String(s) => s.as_str()?.into(),
+        Value::Array(values) => values.iter().map(to_json).collect::<Option<_>>()?,
+        Value::Map(entries) => serde_json::Value::Object(
+            entries
+                .iter()
+                .map(|(k, v)| Some((k.as_str()?.to_string(), to_json(v)?)))
+                .collect::<Option<_>>()?,
+        ),
+        Value::Binary(_) | Value::Ext(..) => return None,
+    })
+}
+
+// Replies built from serde types, such as a saved session
+pub fn from_json(value: serde_json::Value) -> Value {
+    match value {
+        serde_json::Value::Null => Value::Nil,
+        serde_json::Value::Bool(b) => Value::Boolean(b),
+        serde_json::Value::Number(n) => match (n.as_u64(), n.as_i64()) {
+            (Some(n), _) => Value::from(n),
+            (_, Some(n)) => Value::from(n),
+            _ => Value::from(n.as_f64().unwrap_or_default()),
+        },
+        serde_json::Value::String(s) => Value::String(s.into()),
+        serde_json::Value::Array(values) => Value::Array(values.into_iter().map(from_json).collect()),
+        serde_json::Value::Object(entries) => Value::Map(
+            entries
+                .into_iter()
+                .map(|(k, v)| (Value::String(k.into()), from_json(v)))
+                .collect(),
+        ),
+    }
+}
+
+fn as_string(value: &Value, field: &str) -> Result<String, RpcError> {
+    match value.as_str() {
+        Some(value) => Ok(value.to_string()),
+        None => Err(wrong_type(field, "a string", value)),
+    }
+}
+
+fn missing(field: &str) -> RpcError {
+    RpcError::arg(ErrorCode::MissingArgument, field, format!("Missing {field}"))
+}
+
+fn wrong_type(field: &str, expected: &str, value: &Value) -> RpcError {
+    RpcError::arg(
+        ErrorCode::InvalidArgument,
+        field,
+        format!("Expected {field} to be {expected}, got: {value}"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(value: &str) -> Value {
+        Value::String(value.into())
+    }
+
+    #[test]
+    fn decodes_typed_requests() {
+        let args = [
+            string("work"),
+            Value::Map(vec![(string("work"), string("frecency"))]),
+            string("service"),
+        ];
+        assert_eq!(
+            Request::decode(rpc_commands::RPC_WS_LIST, &args).unwrap(),
+            Request::List {
+                list: "work".to_string(),
+                sort: SortArg::PerList(vec![("work".to_string(), SortMode::Frecency)]),
+                tags: vec!["service".to_string()],
+            }
+        );
+    }
+
+    #[test]
+    fn malformed_args_are_errors() {
+        let err = Request::decode(rpc_commands::RPC_WS_DELETE, &[string("work")]).unwrap_err();
+        assert_eq!(err.code, ErrorCode::MissingArgument);
+        assert_eq!(err.field.as_deref(), Some("arg[1] id"));
+
+        let err = Request::decode(rpc_commands::RPC_WS_LIST, &[Value::from(3)]).unwrap_err();
+        assert_eq!(err.code, ErrorCode::InvalidArgument);
+        assert_eq!(err.field.as_deref(), Some("arg[0] list"));
+
+        let replace = Value::Map(vec![
+            (string("Key"), string("id")),
+            (string("New"), Value::Map(vec![(string("Name"), string("name"))])),
+        ]);
+        let err = Request::decode(rpc_commands::RPC_WS_REPLACE, &[string("work"), replace]).unwrap_err();
+        assert_eq!(err.field.as_deref(), Some("arg[1] replace.New.Path"));
+
+        let err = Request::decode(rpc_commands::RPC_WS_LIST_ALL, &[string("newest")]).unwrap_err();
+        assert_eq!(err.code, ErrorCode::InvalidArgument);
+
+        let err = Request::decode("WORKSPACERS.NOPE", &[]).unwrap_err();
+        assert_eq!(err.code, ErrorCode::UnknownMethod);
+        assert_eq!(
+            err.to_value().as_str(),
+            Some(r#"{"code":"unknown_method","message":"Unknown request: WORKSPACERS.NOPE"}"#)
+        );
+    }
+}
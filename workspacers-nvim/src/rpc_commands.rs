@@ -2,34 +2,54 @@ use async_trait::async_trait;
 use common::{
     formatter,
     json::{self, Workspace},
+    session::{self, Session},
+    setup::config::AppConfig,
+    sort::{self, SortMode},
+    store::{StoreError, WorkspaceStore},
+    template::Template,
 };
-use log::{error, info};
+use log::{error, info, warn};
 use nvim_rs::{Handler, Neovim, Value, compat::tokio::Compat};
-use std::{io::Error, path::PathBuf};
+use std::path::{Path, PathBuf};
+
+use crate::rpc_args::{self, ErrorCode, Method, Request, RpcError, SortArg};
+
+pub type NeovimWriter = Compat<tokio::io::Stdout>;
 
 #[derive(Clone)]
 pub struct NeovimHandler {
     pub json_dir: PathBuf,
     pub log_file: PathBuf,
+    pub config: AppConfig,
+    // Parsed display_template from the config
+    pub template: Template,
 }
 
 // Request
-const RPC_WS_LIST: &str = "WORKSPACERS.LIST";
-const RPC_WS_ADD: &str = "WORKSPACERS.ADD";
-const RPC_WS_DELETE: &str = "WORKSPACERS.DELETE";
-const RPC_WS_JSON: &str = "WORKSPACERS.JSON";
-const RPC_WS_PROMOTE: &str = "WORKSPACERS.PROMOTE";
-const RPC_WS_DEMOTE: &str = "WORKSPACERS.DEMOTE";
-const RPC_WS_RECORD: &str = "WORKSPACERS.RECORD";
-const RPC_WS_REPLACE: &str = "WORKSPACERS.REPLACE";
-
-fn rpc_cmd<T>(command_name: &str, result: Result<T, impl std::fmt::Debug>) -> Result<T, Value> {
-    result.map_err(|_| Value::String(format!("Error running {command_name}").into()))
-}
+pub const RPC_WS_LIST: &str = "WORKSPACERS.LIST";
+pub const RPC_WS_LIST_ALL: &str = "WORKSPACERS.LIST_ALL";
+pub const RPC_WS_ADD: &str = "WORKSPACERS.ADD";
+pub const RPC_WS_DELETE: &str = "WORKSPACERS.DELETE";
+pub const RPC_WS_JSON: &str = "WORKSPACERS.JSON";
+pub const RPC_WS_PROMOTE: &str = "WORKSPACERS.PROMOTE";
+pub const RPC_WS_DEMOTE: &str = "WORKSPACERS.DEMOTE";
+pub const RPC_WS_RECORD: &str = "WORKSPACERS.RECORD";
+pub const RPC_WS_REPLACE: &str = "WORKSPACERS.REPLACE";
+pub const RPC_WS_OPENED: &str = "WORKSPACERS.OPENED";
+pub const RPC_WS_ADD_TAG: &str = "WORKSPACERS.ADD_TAG";
+pub const RPC_WS_REMOVE_TAG: &str = "WORKSPACERS.REMOVE_TAG";
+pub const RPC_WS_CONFIG: &str = "WORKSPACERS.CONFIG";
+pub const RPC_WS_HELLO: &str = "WORKSPACERS.HELLO";
+pub const RPC_WS_CURRENT: &str = "WORKSPACERS.CURRENT";
+pub const RPC_WS_SAVE_SESSION: &str = "WORKSPACERS.SAVE_SESSION";
+pub const RPC_WS_LOAD_SESSION: &str = "WORKSPACERS.LOAD_SESSION";
+
+// Bumped when a request or reply changes in a way an older plugin or binary can't handle
+const PROTOCOL_VERSION: u64 = 2;
 
 #[async_trait]
 impl Handler for NeovimHandler {
-    type Writer = Compat<tokio::io::Stdout>;
+    type Writer = NeovimWriter;
 
     // Requests will respond with a Value to lua
     async fn handle_request(
@@ -39,42 +59,60 @@ impl Handler for NeovimHandler {
         _neovim: Neovim<Self::Writer>,
     ) -> Result<Value, Value> {
         info!("REQUEST: {}, {:?}", name, args);
-        let response = handle_req(name, args, &self.json_dir);
-        if response.is_ok() {
-            info!("RESPONSE: {}", response.to_owned().unwrap());
-        } else {
-            error!("ERROR: {}", response.as_ref().err().unwrap());
+        let response = Request::decode(&name, &args)
+            .and_then(|request| handle_req(request, &self.json_dir, &self.config, &self.template));
+        match response {
+            Ok(value) => {
+                info!("RESPONSE: {}", value);
+                Ok(value)
+            }
+            Err(err) => {
+                // The full request is in the log above, lua shows the reason
+                error!("ERROR: {name}: {err} (see log: {})", self.log_file.to_string_lossy());
+                Err(err.to_value())
+            }
         }
-        response
     }
 }
 
-fn handle_req(name: String, args: Vec<Value>, json_dir: &PathBuf) -> Result<Value, Value> {
-    let ws_arg = args[0].as_str().unwrap();
-    let json_path = &json::get_json_file(json_dir, ws_arg);
+fn handle_req(request: Request, json_dir: &Path, config: &AppConfig, template: &Template) -> Result<Value, RpcError> {
+    // Each list is read once per request. A corrupt list is reported rather than treated as empty
+    let open = |list: &str| WorkspaceStore::open(&json::get_list_file(json_dir, list, config.storage));
 
-    if name == RPC_WS_JSON {
-        return Ok(Value::String(json_dir.to_string_lossy().into()));
-    }
-    info!("Received arg[0]: {}", args[0]);
+    match request {
+        // Not tied to a single list so take no list name
+        Request::Hello { client } => Ok(rpc_ws_hello(client)),
+        Request::Config => Ok(rpc_ws_config(config)),
+        Request::Json => Ok(Value::String(json_dir.to_string_lossy().into())),
+        Request::Current { path } => Ok(rpc_ws_current(&json::read_all_workspaces(json_dir)?, &path)),
+        Request::ListAll { sort, tags } => {
+            let lists = json::read_all_workspaces(json_dir)?;
+            Ok(rpc_ws_list_all(&lists, &sort, &tags, config, template))
+        }
 
-    let workspaces = json::read_workspaces(json_path); // Read the json once at the top level 
+        Request::List { list, sort, tags } => Ok(rpc_ws_list(
+            &json::filter_by_tags(open(&list)?.workspaces(), &tags),
+            sort.for_list(&list, config),
+            template,
+        )),
+        Request::Record { list, id } => rpc_ws_record(&open(&list)?, &id),
 
-    match name.as_str() {
-        RPC_WS_LIST => rpc_cmd(RPC_WS_LIST, rpc_ws_list(&workspaces)),
-        RPC_WS_RECORD => rpc_cmd(RPC_WS_RECORD, rpc_ws_record(&workspaces, args)),
+        Request::Add { list, name, path } => rpc_ws_add(&mut open(&list)?, &name, &path),
+        Request::Delete { list, id } => rpc_ws_delete(&mut open(&list)?, json_dir, &list, &id),
 
-        RPC_WS_ADD => rpc_cmd(RPC_WS_ADD, rpc_ws_add(workspaces, json_path, args)),
-        RPC_WS_DELETE => rpc_cmd(RPC_WS_DELETE, rpc_ws_delete(&workspaces, json_path, args)),
+        Request::Promote { list, id } => rpc_ws_promote(&mut open(&list)?, &id),
+        Request::Demote { list, id } => rpc_ws_demote(&mut open(&list)?, &id),
 
-        RPC_WS_PROMOTE => rpc_cmd(RPC_WS_PROMOTE, rpc_ws_promote(&workspaces, json_path, args)),
-        RPC_WS_DEMOTE => rpc_cmd(RPC_WS_DEMOTE, rpc_ws_demote(&workspaces, json_path, args)),
+        Request::Replace { list, id, name, path } => rpc_ws_replace(&mut open(&list)?, &id, &name, &path),
+        Request::Opened { list, id } => rpc_ws_opened(&mut open(&list)?, &id),
 
-        RPC_WS_REPLACE => rpc_cmd(RPC_WS_REPLACE, rpc_ws_replace(workspaces, json_path, args)),
-        _ => {
-            error!("Unknown request: {}", name);
-            Ok(Value::Boolean(false))
+        Request::AddTag { list, id, tag } => rpc_ws_tag(&mut open(&list)?, &id, &tag, true),
+        Request::RemoveTag { list, id, tag } => rpc_ws_tag(&mut open(&list)?, &id, &tag, false),
+
+        Request::SaveSession { list, id, session } => {
+            rpc_ws_save_session(&open(&list)?, json_dir, &list, &id, &session)
         }
+        Request::LoadSession { list, id } => rpc_ws_load_session(json_dir, &list, &id),
     }
 }
 
@@ -83,198 +121,368 @@ fn handle_req(name: String, args: Vec<Value>, json_dir: &PathBuf) -> Result<Valu
 ///    {
 ///        "Fmt": " [ Entry1 ] - [ Path1 ] ",
 ///        "Workspace": {
+///           "Id": "c0ffee00-...",
 ///           "Name": "Entry1"
-///           "Path": "Path1"
+///           "Path": "Path1",
+///           "Highlights": [
+///               { "Column": "name", "Start": 8, "End": 14, "Hl": "WorkspacersName" },
+///               ...
+///           ]
 ///        }
 ///    },
 ///    { ... }
 /// ],
-fn rpc_ws_list(workspaces: &Vec<Workspace>) -> Result<Value, String> {
-    let result = formatter::fmt(workspaces)
+/// Highlights are 0 based, end exclusive byte ranges of each column in Fmt, empty columns are left out
+/// arg[1] is an optional sort mode, see `Args::sort`
+/// arg[2] is an optional tag filter, see `Args::tags`
+fn rpc_ws_list(workspaces: &[Workspace], sort_mode: SortMode, template: &Template) -> Value {
+    let result = formatter::fmt_rows(&sort::sorted(workspaces, sort_mode), template)
         .iter()
-        .map(|(ws_str, ws)| {
-            let mut map = Vec::new();
-
-            let mut workspace_map = Vec::new();
-            workspace_map.push((Value::String("Name".into()), Value::String(ws.name.to_string().into())));
-            workspace_map.push((Value::String("Path".into()), Value::String(ws.path.to_string().into())));
-
-            map.push((
-                Value::String(ws_str.to_string().into()),
-                Value::Map(workspace_map.into()),
-            ));
-            Value::Map(map.into())
+        .map(|row| {
+            let mut ws_map = ws_value(&row.workspace);
+            if let Value::Map(ref mut props) = ws_map {
+                props.push((Value::String("Highlights".into()), highlights_value(&row.spans)));
+            }
+            Value::Map(vec![(Value::String(row.text.to_string().into()), ws_map)])
         })
         .collect::<Vec<Value>>();
 
-    Ok(Value::Array(result))
+    Value::Array(result)
 }
 
-fn rpc_ws_record(workspaces: &Vec<Workspace>, args: Vec<Value>) -> Result<Value, String> {
-    info!("request to pick: {}", args[1]);
-    let arg_pick = args[1].as_str().unwrap();
-    match formatter::fmt(&workspaces)
+/// Same shape as WORKSPACERS.LIST, merged across every list in the json dir
+/// Each workspace includes the list it came from, to pass back as arg[0] of later requests:
+///        "Workspace": {
+///           ...
+///           "List": "work"
+///        }
+/// Highlights include the list name prefix as the "list" column
+/// arg[0] is an optional sort mode, see `Args::sort`. Each list is sorted separately
+/// arg[1] is an optional tag filter, see `Args::tags`
+fn rpc_ws_list_all(
+    lists: &[(String, Vec<Workspace>)],
+    sort: &SortArg,
+    tags: &[String],
+    config: &AppConfig,
+    template: &Template,
+) -> Value {
+    let sorted_lists: Vec<(String, Vec<Workspace>)> = lists
         .iter()
-        .find(|(ws_str, _)| arg_pick.eq(ws_str))
-        .map(|(_, ws)| ws)
-    {
-        Some(ws_match) => {
-            info!("picking: {}", ws_match.name);
-            Ok(Value::Map(vec![
-                (
-                    Value::String("Name".into()),
-                    Value::String(ws_match.name.to_string().into()),
+        .map(|(list_name, workspaces)| {
+            (
+                list_name.to_string(),
+                sort::sorted(
+                    &json::filter_by_tags(workspaces, tags),
+                    sort.for_list(list_name, config),
                 ),
-                (
-                    Value::String("Path".into()),
-                    Value::String(ws_match.path.to_string().into()),
-                ),
-            ]))
-        }
-        None => Err("No matching workspace".to_string()),
-    }
+            )
+        })
+        .collect();
+    let result = formatter::fmt_lists(&sorted_lists, template)
+        .iter()
+        .map(|(row, list_name)| {
+            let mut ws_map = ws_value(&row.workspace);
+            if let Value::Map(ref mut props) = ws_map {
+                props.push((
+                    Value::String("List".into()),
+                    Value::String(list_name.to_string().into()),
+                ));
+                props.push((Value::String("Highlights".into()), highlights_value(&row.spans)));
+            }
+            Value::Map(vec![(Value::String(row.text.to_string().into()), ws_map)])
+        })
+        .collect::<Vec<Value>>();
+
+    Value::Array(result)
 }
 
-fn rpc_ws_add(mut workspaces: Vec<Workspace>, json_file: &PathBuf, args: Vec<Value>) -> Result<Value, Error> {
-    if let Some(ws_arg) = args[1].as_map() {
-        let ws = json::Workspace {
-            name: convert_ws_add(ws_arg, "name")?,
-            path: formatter::unfmt_path(convert_ws_add(ws_arg, "path")?),
-        };
-        info!("req to add: {ws} to json file: {}", json_file.to_string_lossy());
-        workspaces.insert(workspaces.len(), ws);
-        match json::write_workspaces(json_file, &workspaces) {
-            Ok(()) => Ok(Value::Boolean(true)),
-            Err(e) => {
-                error!("Could not write workspace: {}", e);
-                Err(Error::from(std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    format!("Could not write workspace: {e}"),
-                )))
+/// The workspace containing arg[0], a path such as the cwd or a buffer. `~` is expanded and symlinks resolved
+/// When workspaces are nested the deepest one is picked. Replies with nil when no workspace contains the path, else:
+/// {
+///     "Id": "c0ffee00-...",
+///     "Name": "Entry1",
+///     "Path": "Path1",
+///     "Tags": [],
+///     "List": "work"
+/// }
+fn rpc_ws_current(lists: &[(String, Vec<Workspace>)], path: &str) -> Value {
+    match json::find_current(lists, path) {
+        Some((list_name, ws)) => {
+            let mut ws_map = ws_value(&ws);
+            if let Value::Map(ref mut props) = ws_map {
+                props.push((Value::String("List".into()), Value::String(list_name.into())));
             }
+            ws_map
         }
-    } else {
-        // TODO LL
-        Ok(Value::Boolean(false))
+        None => Value::Nil,
     }
 }
 
-fn rpc_ws_delete(workspaces: &Vec<Workspace>, json_file: &PathBuf, args: Vec<Value>) -> Result<Value, Error> {
-    info!("req to del: {}", args[1]);
-    let fmt_vals = formatter::fmt(&workspaces);
-    info!("count before del: {}", workspaces.len());
-    let ws_fmt_arg = args[1].as_str().unwrap();
-    let remaining_ws: Vec<&json::Workspace> = fmt_vals
-        .iter()
-        .filter(|(ws_str, _)| !ws_fmt_arg.eq(ws_str))
-        .map(|(_, ws)| ws)
-        .collect();
-    info!("count after del: {}", remaining_ws.len());
-
-    match json::write_workspaces(json_file, &remaining_ws) {
-        Ok(()) => Ok(Value::Boolean(true)),
-        Err(_) => Err(std::io::Error::new(
-            std::io::ErrorKind::Other,
-            "Could not write workspace",
-        )),
+/// Handshake sent by the plugin on startup to check the binary is up to date
+/// arg[0] is the plugin's protocol version as a string, a string so binaries from before the handshake don't panic
+/// Replies with:
+/// {
+///     "Version": "0.1.0",
+///     "Protocol": 2,
+///     "Methods": [ "WORKSPACERS.LIST", ... ]
+/// }
+fn rpc_ws_hello(client: Option<String>) -> Value {
+    if let Some(client) = client.filter(|client| *client != PROTOCOL_VERSION.to_string()) {
+        warn!("Plugin uses protocol {client}, binary uses {PROTOCOL_VERSION}");
     }
+    Value::Map(vec![
+        (
+            Value::String("Version".into()),
+            Value::String(env!("CARGO_PKG_VERSION").into()),
+        ),
+        (Value::String("Protocol".into()), Value::from(PROTOCOL_VERSION)),
+        (
+            Value::String("Methods".into()),
+            Value::Array(
+                Method::ALL
+                    .iter()
+                    .map(|(_, name)| Value::String((*name).into()))
+                    .collect(),
+            ),
+        ),
+    ])
 }
 
-fn rpc_ws_replace(mut workspaces: Vec<Workspace>, json_file: &PathBuf, args: Vec<Value>) -> Result<Value, Error> {
-    let fmt_vals = formatter::fmt(&workspaces);
-    let arg_pairs = args[1]
-        .as_map()
-        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::Other, "Invalid arguments"))?;
-
-    // Find the key and new values
-    let key = arg_pairs
-        .iter()
-        .find(|(k, _)| k.as_str().unwrap() == "Key")
-        .and_then(|(_, v)| v.as_str())
-        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::Other, "Invalid Key"))?;
+/// Settings from the config the plugin needs, takes no args:
+/// {
+///     "DefaultList": "workspacers",
+///     "Sort": "manual",
+///     "ListSort": { "notes": "frecency" }
+/// }
+fn rpc_ws_config(config: &AppConfig) -> Value {
+    Value::Map(vec![
+        (
+            Value::String("DefaultList".into()),
+            Value::String(config.default_list.to_string().into()),
+        ),
+        (
+            Value::String("Sort".into()),
+            Value::String(config.sort.to_string().into()),
+        ),
+        (
+            Value::String("ListSort".into()),
+            Value::Map(
+                config
+                    .list_sort
+                    .iter()
+                    .map(|(list, sort)| {
+                        (
+                            Value::String(list.to_string().into()),
+                            Value::String(sort.to_string().into()),
+                        )
+                    })
+                    .collect(),
+            ),
+        ),
+    ])
+}
 
-    let new_values = arg_pairs
-        .iter()
-        .find(|(k, _)| k.as_str().unwrap() == "New")
-        .and_then(|(_, v)| v.as_map())
-        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::Other, "Invalid New values"))?;
+// Highlight group of each column: icon -> WorkspacersIcon, last_opened -> WorkspacersLastOpened
+fn highlight_group(column: &str) -> String {
+    let words = column.split('_').map(|word| {
+        let mut chars = word.chars();
+        chars
+            .next()
+            .map(|first| first.to_uppercase().chain(chars).collect::<String>())
+            .unwrap_or_default()
+    });
+    format!("Workspacers{}", words.collect::<String>())
+}
 
-    // Find the workspace to replace
-    if let Some(idx) = fmt_vals.iter().position(|(ws_str, _)| key.eq(ws_str)) {
-        let name_value = new_values
+fn highlights_value(spans: &[formatter::Span]) -> Value {
+    Value::Array(
+        spans
             .iter()
-            .find(|(k, _)| k.as_str().unwrap() == "Name")
-            .and_then(|(_, v)| v.as_str())
-            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::Other, "Missing Name"))?;
-        let path_value = new_values
-            .iter()
-            .find(|(k, _)| k.as_str().unwrap() == "Path")
-            .and_then(|(_, v)| v.as_str())
-            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::Other, "Missing Path"))?;
-
-        workspaces[idx] = json::Workspace {
-            name: name_value.to_string(),
-            path: formatter::unfmt_path(path_value.to_string()),
-        };
-
-        // Write updated workspaces
-        json::write_workspaces(json_file, &workspaces)
-            .map(|_| Value::Boolean(true))
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, format!("Write error: {e}")).into())
-    } else {
-        Err(std::io::Error::new(std::io::ErrorKind::Other, "Workspace not found").into())
+            .map(|span| {
+                Value::Map(vec![
+                    (Value::String("Column".into()), Value::String(span.column.into())),
+                    (Value::String("Start".into()), Value::from(span.start as u64)),
+                    (Value::String("End".into()), Value::from(span.end as u64)),
+                    (
+                        Value::String("Hl".into()),
+                        Value::String(highlight_group(span.column).into()),
+                    ),
+                ])
+            })
+            .collect(),
+    )
+}
+
+fn ws_value(ws: &Workspace) -> Value {
+    Value::Map(vec![
+        (Value::String("Id".into()), Value::String(ws.id.to_string().into())),
+        (Value::String("Name".into()), Value::String(ws.name.to_string().into())),
+        (Value::String("Path".into()), Value::String(ws.path.to_string().into())),
+        (
+            Value::String("Tags".into()),
+            Value::Array(
+                ws.tags
+                    .iter()
+                    .map(|tag| Value::String(tag.to_string().into()))
+                    .collect(),
+            ),
+        ),
+    ])
+}
+
+fn rpc_ws_record(store: &WorkspaceStore, id: &str) -> Result<Value, RpcError> {
+    info!("request to pick: {id}");
+    match store.find_by_id(id) {
+        Some(ws_match) => {
+            info!("picking: {}", ws_match.name);
+            Ok(ws_value(ws_match))
+        }
+        None => Err(StoreError::NotFound(id.to_string()).into()),
     }
 }
 
-fn rpc_ws_promote(workspaces: &Vec<Workspace>, json_file: &PathBuf, args: Vec<Value>) -> Result<Value, Error> {
-    let fmt_vals = formatter::fmt(workspaces);
-    let ws_fmt_arg = args[1].as_str().unwrap();
-    let mut new_idx = 0;
-    let mut new_workspaces: Vec<&json::Workspace> = fmt_vals.iter().map(|(_, ws)| ws).collect();
-    if let Some(idx) = fmt_vals.iter().position(|(ws_str, _)| ws_fmt_arg.eq(ws_str)) {
-        let target_idx = if idx == 0 { new_workspaces.len() - 1 } else { idx - 1 };
-        let ws = new_workspaces.remove(idx);
-        new_workspaces.insert(target_idx, ws);
-        new_idx = target_idx;
+fn rpc_ws_opened(store: &mut WorkspaceStore, id: &str) -> Result<Value, RpcError> {
+    info!("req to record open: {id}");
+    store.record_opened(id)?;
+    Ok(Value::Boolean(true))
+}
+
+/// arg[1] is the workspace id, arg[2] the tag. Returns the workspace's tags
+fn rpc_ws_tag(store: &mut WorkspaceStore, id: &str, tag: &str, add: bool) -> Result<Value, RpcError> {
+    info!("req to {} tag: {tag} on {id}", if add { "add" } else { "remove" });
+    let tags = match add {
+        true => store.add_tag(id, tag),
+        false => store.remove_tag(id, tag),
+    }?;
+    Ok(Value::Array(
+        tags.into_iter().map(|tag| Value::String(tag.into())).collect(),
+    ))
+}
+
+/// arg[1] is the new workspace: { name = "...", path = "..." }
+fn rpc_ws_add(store: &mut WorkspaceStore, name: &str, path: &str) -> Result<Value, RpcError> {
+    let ws = json::Workspace::new(
+        formatter::unfmt_ws_value(name),
+        formatter::unfmt_path(formatter::unfmt_ws_value(path)),
+    );
+    store
+        .add(ws)
+        .inspect_err(|e| error!("Could not write workspace: {e}"))?;
+    Ok(Value::Boolean(true))
+}
+
+fn rpc_ws_delete(store: &mut WorkspaceStore, json_dir: &Path, list: &str, id: &str) -> Result<Value, RpcError> {
+    info!("req to del: {id}");
+    match store.remove(id) {
+        // Already being removed by another process is not an error
+        Ok(_) | Err(StoreError::NotFound(_)) => {}
+        Err(StoreError::Conflict(err)) if matches!(*err, StoreError::NotFound(_)) => {}
+        Err(err) => return Err(err.into()),
     }
-    match json::write_workspaces(json_file, &new_workspaces) {
-        Ok(()) => Ok(Value::Integer(new_idx.into())), // Return the new index
-        Err(_) => Err(std::io::Error::new(
-            std::io::ErrorKind::Other,
-            "Could not write workspace",
-        )),
+    // The workspace is gone either way, a leftover session is only logged
+    if let Err(err) = session::remove_session(json_dir, list, id) {
+        warn!("Could not remove session of {id}: {err}");
     }
+    Ok(Value::Boolean(true))
 }
 
-fn rpc_ws_demote(workspaces: &Vec<Workspace>, json_file: &PathBuf, args: Vec<Value>) -> Result<Value, Error> {
-    let fmt_vals = formatter::fmt(workspaces);
-    let ws_fmt_arg = args[1].as_str().unwrap();
-    let mut new_idx = 0;
-    let mut new_workspaces: Vec<&json::Workspace> = fmt_vals.iter().map(|(_, ws)| ws).collect();
-    if let Some(idx) = fmt_vals.iter().position(|(ws_str, _)| ws_fmt_arg.eq(ws_str)) {
-        let target_idx = if idx == new_workspaces.len() - 1 { 0 } else { idx + 1 };
-        let ws = new_workspaces.remove(idx);
-        new_workspaces.insert(target_idx, ws);
-        new_idx = target_idx;
-    }
-    match json::write_workspaces(json_file, &new_workspaces) {
-        Ok(()) => Ok(Value::Integer(new_idx.into())), // Return the new index
-        Err(_) => Err(std::io::Error::new(
-            std::io::ErrorKind::Other,
-            "Could not write workspace",
-        )),
+/// Saves the editor state of a workspace, sent when leaving it
+/// arg[0] is the list, arg[1] the workspace id and arg[2] the session:
+/// {
+///     "Cwd": "/src/app",
+///     "Buffers": [ "/src/app/main.rs", ... ],
+///     "Layout": { "Row": [
+///         { "Leaf": { "Path": "/src/app/main.rs", "Line": 10, "Col": 4, "Width": 80, "Height": 40, "Current": true } },
+///         { "Col": [ ... ] }
+///     ] }
+/// }
+/// Layout follows winlayout(), Row is side by side and Col is stacked
+fn rpc_ws_save_session(
+    store: &WorkspaceStore,
+    json_dir: &Path,
+    list: &str,
+    id: &str,
+    session: &Session,
+) -> Result<Value, RpcError> {
+    if json::position_by_id(store.workspaces(), id).is_none() {
+        return Err(StoreError::NotFound(id.to_string()).into());
     }
+    session::write_session(json_dir, list, id, session)
+        .map_err(|err| RpcError::new(ErrorCode::WriteFailed, format!("Could not save session of {id}: {err}")))?;
+    Ok(Value::Boolean(true))
 }
 
-pub fn convert_ws_add(obj: &Vec<(Value, Value)>, prop: &str) -> Result<String, Error> {
-    if let Some(prop_match) = obj.iter().find(|p| p.0.as_str().unwrap() == prop) {
-        info!("match to add: {}", &prop_match.1.to_string());
-        Ok(formatter::unfmt_ws_value(&prop_match.1.to_string()))
-    } else {
-        error!("could not read workspace add data - inner");
-        Err(std::io::Error::new(
-            std::io::ErrorKind::Other,
-            "could not read workspace add data - inner",
-        ))
+/// The session saved by WORKSPACERS.SAVE_SESSION for the list in arg[0] and workspace id in arg[1],
+/// nil when none was saved
+fn rpc_ws_load_session(json_dir: &Path, list: &str, id: &str) -> Result<Value, RpcError> {
+    let session = session::read_session(json_dir, list, id)
+        .map_err(|err| RpcError::new(ErrorCode::ReadFailed, format!("Could not read session of {id}: {err}")))?;
+    Ok(match session {
+        Some(session) => {
+            rpc_args::from_json(serde_json::to_value(session).map_err(|err| {
+                RpcError::new(ErrorCode::ReadFailed, format!("Could not read session of {id}: {err}"))
+            })?)
+        }
+        None => Value::Nil,
+    })
+}
+
+/// arg[1] is the workspace id and its new values: { Key = "<id>", New = { Name = "...", Path = "..." } }
+fn rpc_ws_replace(store: &mut WorkspaceStore, id: &str, name: &str, path: &str) -> Result<Value, RpcError> {
+    store.update(id, |ws| {
+        ws.name = name.to_string();
+        ws.path = formatter::unfmt_path(path.to_string());
+    })?;
+    Ok(Value::Boolean(true))
+}
+
+fn rpc_ws_promote(store: &mut WorkspaceStore, id: &str) -> Result<Value, RpcError> {
+    let target_idx = store.promote(id)?;
+    Ok(Value::Integer(target_idx.into())) // Return the new index
+}
+
+fn rpc_ws_demote(store: &mut WorkspaceStore, id: &str) -> Result<Value, RpcError> {
+    let target_idx = store.demote(id)?;
+    Ok(Value::Integer(target_idx.into())) // Return the new index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(value: &str) -> Value {
+        Value::String(value.into())
+    }
+
+    // Args that `method` decodes from, args it has no use for are ignored
+    fn args(method: &str) -> Vec<Value> {
+        let map = rpc_args::from_json;
+        match method {
+            RPC_WS_LIST => vec![string("work"), string("manual"), string("tag")],
+            RPC_WS_LIST_ALL => vec![string("manual"), string("tag")],
+            RPC_WS_ADD => vec![string("work"), map(serde_json::json!({ "name": "ws", "path": "/ws" }))],
+            RPC_WS_REPLACE => vec![
+                string("work"),
+                map(serde_json::json!({ "Key": "id", "New": { "Name": "ws", "Path": "/ws" } })),
+            ],
+            RPC_WS_SAVE_SESSION => vec![
+                string("work"),
+                string("id"),
+                map(serde_json::json!({
+                    "Cwd": "/ws",
+                    "Layout": { "Leaf": { "Line": 1, "Col": 0, "Width": 80, "Height": 24 } }
+                })),
+            ],
+            _ => vec![string("work"), string("id"), string("tag")],
+        }
+    }
+
+    #[test]
+    fn every_method_decodes() {
+        for (_, name) in Method::ALL {
+            if let Err(err) = Request::decode(name, &args(name)) {
+                panic!("{name} did not decode: {err}");
+            }
+        }
     }
 }
@@ -0,0 +1,176 @@
+use common::json;
+use log::{error, info};
+use notify::event::{EventKind, ModifyKind};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use nvim_rs::{Neovim, Value};
+use std::collections::BTreeSet;
+use std::io::Error;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use crate::rpc_commands::NeovimWriter;
+
+// Sent as the `event` of the WorkspacersChanged autocmd
+pub const RPC_WS_CHANGED: &str = "WORKSPACERS.CHANGED";
+// Neovim treats notifications from a job as API calls, so changes are sent as a User autocmd lua can listen for
+const AUTOCMD_PATTERN: &str = "WorkspacersChanged";
+
+// A write is a temp file, backup rotation and rename, so changes are collected for a moment and sent once per list
+const DEBOUNCE: Duration = Duration::from_millis(150);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ChangeKind {
+    Created,
+    Modified,
+    Removed,
+}
+
+impl ChangeKind {
+    fn name(self) -> &'static str {
+        match self {
+            ChangeKind::Created => "created",
+            ChangeKind::Modified => "modified",
+            ChangeKind::Removed => "removed",
+        }
+    }
+}
+
+// Watches the lists in `json_dir`, firing `User WorkspacersChanged` with { event, list, kind } in Neovim on changes
+// The watcher stops when dropped
+pub fn watch(json_dir: &Path, nvim: Neovim<NeovimWriter>) -> Result<RecommendedWatcher, Error> {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| match event {
+        Ok(event) => {
+            for list in changed_lists(&event) {
+                // Only fails once the receiver is gone, when the plugin is closing
+                let _ = sender.send(list);
+            }
+        }
+        Err(err) => error!("Watch error: {err}"),
+    })
+    .map_err(Error::other)?;
+    watcher
+        .watch(json_dir, RecursiveMode::NonRecursive)
+        .map_err(Error::other)?;
+    info!("Watching: {}", json_dir.to_string_lossy());
+
+    tokio::spawn(send_changes(json_dir.to_path_buf(), receiver, nvim));
+    Ok(watcher)
+}
+
+fn changed_lists(event: &notify::Event) -> Vec<String> {
+    match event.kind {
+        EventKind::Create(_)
+        | EventKind::Remove(_)
+        | EventKind::Modify(ModifyKind::Data(_) | ModifyKind::Name(_) | ModifyKind::Any) => event
+            .paths
+            .iter()
+            .filter_map(|path| json::get_list_name(path))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+// Lists in the json dir, unreadable dirs have none
+fn list_names(json_dir: &Path) -> BTreeSet<String> {
+    json::get_list_names(json_dir).unwrap_or_default().into_iter().collect()
+}
+
+// The kind of change is worked out from the lists before and after, as a write replaces the list file
+fn change_kinds(
+    changed: BTreeSet<String>,
+    before: &BTreeSet<String>,
+    after: &BTreeSet<String>,
+) -> Vec<(String, ChangeKind)> {
+    changed
+        .into_iter()
+        .filter_map(|list| {
+            let kind = match (before.contains(&list), after.contains(&list)) {
+                (false, true) => ChangeKind::Created,
+                (true, true) => ChangeKind::Modified,
+                (true, false) => ChangeKind::Removed,
+                // Created and removed again before it was seen
+                (false, false) => return None,
+            };
+            Some((list, kind))
+        })
+        .collect()
+}
+
+async fn send_changes(json_dir: PathBuf, mut receiver: mpsc::UnboundedReceiver<String>, nvim: Neovim<NeovimWriter>) {
+    let mut known = list_names(&json_dir);
+    while let Some(list) = receiver.recv().await {
+        let mut changed = BTreeSet::from([list]);
+        tokio::time::sleep(DEBOUNCE).await;
+        while let Ok(list) = receiver.try_recv() {
+            changed.insert(list);
+        }
+
+        let lists = list_names(&json_dir);
+        let changes = change_kinds(changed, &known, &lists);
+        known = lists;
+
+        for (list, kind) in changes {
+            info!("NOTIFY: {RPC_WS_CHANGED} {list} {}", kind.name());
+            let data = Value::Map(vec![
+                (Value::String("event".into()), Value::String(RPC_WS_CHANGED.into())),
+                (Value::String("list".into()), Value::String(list.to_string().into())),
+                (Value::String("kind".into()), Value::String(kind.name().into())),
+            ]);
+            let opts = vec![
+                (Value::String("pattern".into()), Value::String(AUTOCMD_PATTERN.into())),
+                (Value::String("modeline".into()), Value::Boolean(false)),
+                (Value::String("data".into()), data),
+            ];
+            if let Err(err) = nvim.exec_autocmds(Value::String("User".into()), opts).await {
+                error!("Failed to notify Neovim of a change to '{list}': {err}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::event::{AccessKind, CreateKind};
+
+    fn lists(names: &[&str]) -> BTreeSet<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn kinds_come_from_the_lists_before_and_after() {
+        let changes = change_kinds(
+            lists(&["new", "kept", "gone", "brief"]),
+            &lists(&["kept", "gone", "other"]),
+            &lists(&["new", "kept", "other"]),
+        );
+        assert_eq!(
+            changes,
+            vec![
+                ("gone".to_string(), ChangeKind::Removed),
+                ("kept".to_string(), ChangeKind::Modified),
+                ("new".to_string(), ChangeKind::Created),
+            ]
+        );
+    }
+
+    #[test]
+    fn only_list_files_are_changes() {
+        let paths = [
+            "/data/work.json",
+            "/data/work.json.bak",
+            "/data/work.lock",
+            "/data/notes.toml",
+        ];
+        let event = paths.iter().fold(
+            notify::Event::new(EventKind::Create(CreateKind::File)),
+            |event, path| event.add_path(PathBuf::from(path)),
+        );
+        assert_eq!(changed_lists(&event), vec!["work", "notes"]);
+
+        let read = notify::Event::new(EventKind::Access(AccessKind::Any)).add_path(PathBuf::from("/data/work.json"));
+        assert!(changed_lists(&read).is_empty());
+    }
+}